@@ -0,0 +1,356 @@
+use ado::{Error, Recurrence, Status, Task, TodoList};
+#[cfg(feature = "json")]
+use ado::{BasicTask, ResultIter};
+
+use std::io;
+
+use super::{ancestors, Archive, Check, FrontError, FrontResult, Import, Reorder};
+
+/// Runs a non-interactive command, such as `add`, against the list.
+/// `args` are the command line arguments after the program name.
+pub fn command<T>(todo_list: &mut T, args: &[String]) -> FrontResult<()>
+    where T: Import<Error = Error> + Reorder + Check + Archive,
+          T::Task: Task<Error = Error>
+{
+    match args[0].as_str() {
+        "add" => add(todo_list, &args[1..]),
+        "list" => list(todo_list, &args[1..]),
+        "done" => done(todo_list, &args[1..]),
+        "depend" => depend(todo_list, &args[1..]),
+        "subtask" => subtask(todo_list, &args[1..]),
+        "repeat" => repeat(todo_list, &args[1..]),
+        "export" => export(todo_list, &args[1..]),
+        "import" => import(todo_list, &args[1..]),
+        "check" => check(todo_list, &args[1..]),
+        "archive" => archive(todo_list, &args[1..]),
+        _ => Err(FrontError::NoSuchCommand),
+    }
+}
+
+/// `ado add <summary>` creates a task and prints its id.
+/// The words of the summary don't need to be quoted.
+fn add<T>(todo_list: &mut T, args: &[String]) -> FrontResult<()>
+    where T: TodoList<Error = Error>
+{
+    if args.is_empty() {
+        return Err(FrontError::Usage("ado add <summary>"));
+    }
+    let id = todo_list.create(&args.join(" "))?;
+    println!("{}", id);
+    Ok(())
+}
+
+/// `ado list [--status <status>]` prints one task per line, as its id,
+/// status and name separated by tabs, in the list's order.
+/// The status is one of `open`, `done` or `wont`.
+///
+/// With `--json`, the tasks are printed as a JSON array of `ListEntry`.
+fn list<T>(todo_list: &T, args: &[String]) -> FrontResult<()>
+    where T: TodoList<Error = Error>
+{
+    #[cfg(not(feature = "json"))]
+    const USAGE: &str = "ado list [--status <status>]";
+    #[cfg(feature = "json")]
+    const USAGE: &str = "ado list [--status <status>] [--json]";
+
+    let mut status = None;
+    #[cfg(feature = "json")]
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--status" => {
+                let name = args.next().ok_or(FrontError::Usage(USAGE))?;
+                status = Some(parse_status(name)?);
+            }
+            #[cfg(feature = "json")]
+            "--json" => json = true,
+            _ => return Err(FrontError::Usage(USAGE)),
+        }
+    }
+
+    let tasks = match status {
+        Some(status) => todo_list.with_status(status),
+        None => todo_list.enumerate(),
+    };
+    #[cfg(feature = "json")]
+    {
+        if json {
+            return print_json(tasks);
+        }
+    }
+    for result in tasks {
+        let (id, task) = result?;
+        let task = task.projection();
+        println!("{}\t{}\t{}", id, task.status.name(), task.name);
+    }
+    Ok(())
+}
+
+/// One task in the output of `ado list --json`: its id alongside every
+/// field of the task, e.g.
+/// `{"id": 1, "status": "open", "name": "buy milk", "due": null, ...}`.
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct ListEntry {
+    id: usize,
+    #[serde(flatten)]
+    task: BasicTask,
+}
+
+#[cfg(feature = "json")]
+fn print_json<'a, T>(tasks: ResultIter<'a, (usize, &'a T)>) -> FrontResult<()>
+    where T: Task
+{
+    let entries = tasks.map(|result| {
+            result.map(|(id, task)| {
+                ListEntry {
+                    id,
+                    task: task.projection(),
+                }
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let json = ::serde_json::to_string_pretty(&entries)
+        .map_err(Error::external)?;
+    println!("{}", json);
+    Ok(())
+}
+
+/// `ado done <id>` marks the task with the given id as Done, unless it
+/// depends on tasks which aren't Done yet.
+fn done<T>(todo_list: &mut T, args: &[String]) -> FrontResult<()>
+    where T: TodoList<Error = Error>,
+          T::Task: Task<Error = Error>
+{
+    const USAGE: &str = "ado done <id>";
+    let id = match args {
+        [id] => id.parse::<usize>().map_err(|_| FrontError::Usage(USAGE))?,
+        _ => return Err(FrontError::Usage(USAGE)),
+    };
+    todo_list.goto_status(id, Status::Done)?;
+    Ok(())
+}
+
+/// `ado depend <id> <dependency>` stops the first task from being marked
+/// as Done until the second one is.
+fn depend<T>(todo_list: &mut T, args: &[String]) -> FrontResult<()>
+    where T: TodoList<Error = Error>,
+          T::Task: Task<Error = Error>
+{
+    const USAGE: &str = "ado depend <id> <dependency>";
+    let (id, dependency) = match args {
+        [id, dependency] => {
+            (id.parse::<usize>().map_err(|_| FrontError::Usage(USAGE))?,
+             dependency.parse::<usize>().map_err(|_| FrontError::Usage(USAGE))?)
+        }
+        _ => return Err(FrontError::Usage(USAGE)),
+    };
+    todo_list.add_dependency(id, dependency)?;
+    Ok(())
+}
+
+/// `ado subtask <id> <parent>` moves the first task under the second.
+///
+/// The task is also moved in the list's own order, to after the
+/// parent's other subtasks, so that the picker shows it under its
+/// parent without reading every task to work out where it goes.
+fn subtask<T>(todo_list: &mut T, args: &[String]) -> FrontResult<()>
+    where T: Reorder<Error = Error>,
+          T::Task: Task<Error = Error>
+{
+    const USAGE: &str = "ado subtask <id> <parent>";
+    let (id, parent) = match args {
+        [id, parent] => {
+            (id.parse::<usize>().map_err(|_| FrontError::Usage(USAGE))?,
+             parent.parse::<usize>().map_err(|_| FrontError::Usage(USAGE))?)
+        }
+        _ => return Err(FrontError::Usage(USAGE)),
+    };
+    todo_list.set_parent(id, Some(parent))?;
+
+    let others = todo_list.ids()
+        .filter(|result| result.as_ref().ok() != Some(&id))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut index = others.iter()
+        .position(|&other| other == parent)
+        .ok_or(Error::NotFound(parent))? + 1;
+    while index < others.len() && ancestors(todo_list, others[index])?.contains(&parent) {
+        index += 1;
+    }
+    todo_list.move_to(id, index)?;
+    Ok(())
+}
+
+/// `ado repeat <id> <daily|weekly|monthly|never>` sets how often a task
+/// repeats. Marking a repeating task as Done creates its next occurrence.
+fn repeat<T>(todo_list: &mut T, args: &[String]) -> FrontResult<()>
+    where T: TodoList<Error = Error>,
+          T::Task: Task<Error = Error>
+{
+    const USAGE: &str = "ado repeat <id> <daily|weekly|monthly|never>";
+    let (id, recurrence) = match args {
+        [id, recurrence] => {
+            let id = id.parse::<usize>().map_err(|_| FrontError::Usage(USAGE))?;
+            match recurrence.as_str() {
+                "never" => (id, None),
+                name => (id, Some(name.parse::<Recurrence>()?)),
+            }
+        }
+        _ => return Err(FrontError::Usage(USAGE)),
+    };
+    todo_list.find_mut(id)?.set_recurrence(recurrence)?;
+    Ok(())
+}
+
+/// `ado export --format <format>` prints the whole list in another
+/// format: `markdown`, `csv` or `icalendar`.
+fn export<T>(todo_list: &T, args: &[String]) -> FrontResult<()>
+    where T: TodoList<Error = Error>
+{
+    const USAGE: &str = "ado export --format <markdown|csv|icalendar>";
+    let format = match args {
+        [flag, format] if flag == "--format" => format.as_str(),
+        _ => return Err(FrontError::Usage(USAGE)),
+    };
+    let exported = match format {
+        "markdown" => todo_list.to_markdown()?,
+        "csv" => todo_list.to_csv()?,
+        "icalendar" => todo_list.to_icalendar()?,
+        _ => return Err(FrontError::Usage(USAGE)),
+    };
+    print!("{}", exported);
+    Ok(())
+}
+
+/// `ado import < <file>` creates a task for each non-empty line of
+/// standard input, and prints how many were imported.
+fn import<T>(todo_list: &mut T, args: &[String]) -> FrontResult<()>
+    where T: Import<Error = Error>
+{
+    if !args.is_empty() {
+        return Err(FrontError::Usage("ado import < <file>"));
+    }
+    let stdin = io::stdin();
+    let ids = todo_list.import_lines(stdin.lock())?;
+    println!("Imported {} tasks", ids.len());
+    Ok(())
+}
+
+/// `ado check` reads every task file without changing any of them,
+/// printing each file which was skipped because it isn't a task file,
+/// then each one which can't be read and why, and fails if there were
+/// any of the latter.
+fn check<T>(todo_list: &T, args: &[String]) -> FrontResult<()>
+    where T: Check<Error = Error>
+{
+    if !args.is_empty() {
+        return Err(FrontError::Usage("ado check"));
+    }
+    let report = todo_list.check()?;
+    for file_name in report.skipped.iter() {
+        println!("{}: skipped, not a task file", file_name.display());
+    }
+    for (file_name, err) in report.unreadable.iter() {
+        println!("{}: {}", file_name.display(), err);
+    }
+    if !report.unreadable.is_empty() {
+        return Err(FrontError::Unreadable(report.unreadable.len()));
+    }
+    println!("All task files could be read");
+    Ok(())
+}
+
+/// `ado archive` moves every done task out of the list and prints how
+/// many were moved, and `ado archive --list` prints the archived tasks
+/// in the same way as `list`.
+fn archive<T>(todo_list: &mut T, args: &[String]) -> FrontResult<()>
+    where T: Archive<Error = Error>
+{
+    match args {
+        [] => {
+            let archived = todo_list.archive_done()?;
+            println!("Archived {} tasks", archived);
+        }
+        [flag] if flag == "--list" => {
+            for (id, task) in todo_list.list_archived()? {
+                println!("{}\t{}\t{}", id, task.status.name(), task.name);
+            }
+        }
+        _ => return Err(FrontError::Usage("ado archive [--list]")),
+    }
+    Ok(())
+}
+
+/// Parses a status by its lowercase name, as printed by `list`.
+fn parse_status(name: &str) -> FrontResult<Status> {
+    [Status::Open, Status::Done, Status::Wont]
+        .iter()
+        .find(|status| status.name() == name)
+        .cloned()
+        .ok_or_else(|| FrontError::Ado(Error::InvalidStatus(String::from(name))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ado::core::FakeTodoList;
+    use tests::TempDir;
+    use {FileTodoList, Lines};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&arg| String::from(arg)).collect()
+    }
+
+    #[test]
+    fn done_marks_the_task_as_done() {
+        let mut todo_list = FakeTodoList::with_tasks(vec![("a", Status::Open), ("b", Status::Open)]);
+        done(&mut todo_list, &args(&["1"])).unwrap();
+        assert_eq!(Status::Open, todo_list.find(0).unwrap().view().status);
+        assert_eq!(Status::Done, todo_list.find(1).unwrap().view().status);
+    }
+
+    #[test]
+    fn done_with_an_unknown_id_is_an_error() {
+        let mut todo_list = FakeTodoList::with_tasks(vec![("a", Status::Open)]);
+        assert!(matches!(done(&mut todo_list, &args(&["5"])),
+                         Err(FrontError::Ado(Error::NotFound(5)))));
+    }
+
+    #[test]
+    fn done_needs_exactly_one_id() {
+        let mut todo_list = FakeTodoList::with_tasks(vec![("a", Status::Open)]);
+        for bad in [&[][..], &["a"][..], &["0", "0"][..]].iter() {
+            assert!(matches!(done(&mut todo_list, &args(bad)), Err(FrontError::Usage(_))));
+        }
+        assert_eq!(Status::Open, todo_list.find(0).unwrap().view().status);
+    }
+
+    #[test]
+    fn subtask_moves_the_task_after_its_parents_other_subtasks() {
+        let dir = TempDir::new("subtask_moves_the_task_after_its_parents_other_subtasks");
+        {
+            let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+            todo_list.create_all(&["a", "b", "c", "d"]).unwrap();
+            command(&mut todo_list, &args(&["subtask", "4", "1"])).unwrap();
+            command(&mut todo_list, &args(&["subtask", "3", "1"])).unwrap();
+            command(&mut todo_list, &args(&["subtask", "2", "4"])).unwrap();
+        }
+        let todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        assert_eq!(vec![1, 4, 2, 3], todo_list.ids().collect::<ado::Result<Vec<_>>>().unwrap());
+        assert_eq!(Some(4), todo_list.find(2).unwrap().view().parent);
+    }
+
+    #[test]
+    fn done_is_saved_to_the_task_file() {
+        let dir = TempDir::new("done_is_saved_to_the_task_file");
+        let id = {
+            let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+            let id = todo_list.create("a").unwrap();
+            command(&mut todo_list, &args(&["done", &id.to_string()])).unwrap();
+            id
+        };
+        let todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        assert_eq!(Status::Done, todo_list.find(id).unwrap().view().status);
+    }
+}
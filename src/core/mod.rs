@@ -1,58 +1,514 @@
+use chrono::{DateTime, Days, Months, NaiveDate, Utc};
 use vec_map::VecMap;
 
+use std::borrow::Cow;
+use std::cmp;
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 #[derive(Debug)]
 pub enum Error {
     AlreadyDone,
     AlreadyWont,
-    External(Box<::std::error::Error>),
+    Blocked(usize),
+    DependencyCycle { id: usize, dependency: usize },
+    DuplicateTask(usize),
+    EmptyName,
+    External(Box<dyn std::error::Error>),
+    InvalidName(String),
+    InvalidRecurrence(String),
+    InvalidStatus(String),
+    InvalidTag(String),
+    /// Reading or writing a file failed, as opposed to an `External`
+    /// error from a library.
+    Io(::std::io::Error),
+    Locked,
+    MalformedTask { id: usize, reason: String },
+    /// There was no task to use, such as when a name matches none.
     NoSuchTask,
+    /// No task has the given id.
+    NotFound(usize),
+    ParentCycle { id: usize, parent: usize },
+}
+
+impl Error {
+    /// Wraps an error from outside ado, keeping it as the source.
+    pub fn external<E>(cause: E) -> Error
+        where E: ::std::error::Error + 'static
+    {
+        Error::External(Box::new(cause))
+    }
 }
 
 impl From<::std::fmt::Error> for Error {
     fn from(cause: ::std::fmt::Error) -> Error {
-        Error::External(Box::new(cause))
+        Error::external(cause)
     }
 }
 
 impl From<::std::io::Error> for Error {
     fn from(cause: ::std::io::Error) -> Error {
-        Error::External(Box::new(cause))
+        Error::Io(cause)
     }
 }
 
-pub type ResultIter<'a, T, E = Error> = Box<Iterator<Item = Result<T, E>> + 'a>;
+impl From<::std::num::ParseIntError> for Error {
+    fn from(cause: ::std::num::ParseIntError) -> Error {
+        Error::external(cause)
+    }
+}
+
+impl From<ParseStatusError> for Error {
+    fn from(cause: ParseStatusError) -> Error {
+        Error::InvalidStatus(cause.input)
+    }
+}
+
+/// The error produced when a string does not name a `Status`.
+#[derive(Debug, Clone)]
+pub struct ParseStatusError {
+    input: String,
+}
+
+impl ParseStatusError {
+    /// The string which failed to parse.
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+}
+
+pub type ResultIter<'a, T, E = Error> = Box<dyn Iterator<Item = Result<T, E>> + 'a>;
 pub type Result<T, E = Error> = ::std::result::Result<T, E>;
 
 pub trait TodoList {
     type Error: ::std::error::Error + From<<Self::Task as Task>::Error>;
     type Task: Task;
 
+    /// Creates an open task, failing with `EmptyName` if the name is
+    /// blank or `InvalidName` if it contains a line break.
     fn create(&mut self, name: &str) -> Result<usize, Self::Error>;
 
-    fn iter(&self) -> ResultIter<&Self::Task, Self::Error>;
-    fn iter_mut(&mut self) -> ResultIter<&mut Self::Task, Self::Error>;
+    /// Creates a task for each name, returning their ids in the same order.
+    fn create_all(&mut self, names: &[&str]) -> Result<Vec<usize>, Self::Error> {
+        names.iter()
+            .map(|name| self.create(name))
+            .collect()
+    }
+
+    fn iter(&self) -> ResultIter<'_, &Self::Task, Self::Error>;
+    fn iter_mut(&mut self) -> ResultIter<'_, &mut Self::Task, Self::Error>;
     fn into_iter<'a>(self) -> ResultIter<'a, Self::Task, Self::Error>;
 
     /// Enumerate, ids, and sorted, must produce results in
     /// an order which is consistent with eachother.
-    fn enumerate(&self) -> ResultIter<(usize, &Self::Task), Self::Error>;
-    fn ids(&self) -> ResultIter<usize, Self::Error> {
+    fn enumerate(&self) -> ResultIter<'_, (usize, &Self::Task), Self::Error>;
+    fn ids(&self) -> ResultIter<'_, usize, Self::Error> {
         // By default we can drop the tasks from the enumerate output.
         Box::new(self.enumerate()
             .map(|result| result.map(|(id, _)| id)))
     }
-    fn sorted(&self) -> ResultIter<&Self::Task, Self::Error> {
+    fn sorted(&self) -> ResultIter<'_, &Self::Task, Self::Error> {
         // By default we can drop the ids from the enumerate output.
         Box::new(self.enumerate()
             .map(|result| result.map(|(_, task)| task)))
     }
 
+    /// The number of tasks in the list. Backends which know it without
+    /// visiting every task should override this.
+    fn len(&self) -> Result<usize, Self::Error> {
+        let mut len = 0;
+        for result in self.ids() {
+            result?;
+            len += 1;
+        }
+        Ok(len)
+    }
+
+    fn is_empty(&self) -> Result<bool, Self::Error> {
+        Ok(self.len()? == 0)
+    }
+
+    /// The number of tasks in the list, the same as `len`.
+    fn count(&self) -> Result<usize, Self::Error> {
+        self.len()
+    }
+
+    /// The number of tasks with each status, keyed by `Status::name`.
+    /// Every status has an entry, even if no task has that status.
+    fn count_by_status(&self) -> Result<HashMap<String, usize>, Self::Error> {
+        let mut counts = [Status::Open, Status::Done, Status::Wont]
+            .iter()
+            .map(|status| (String::from(status.name()), 0))
+            .collect::<HashMap<_, _>>();
+        for result in self.enumerate() {
+            let (_, task) = result?;
+            *counts.entry(String::from(task.view().status.name())).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    /// The list as a GitHub-style checklist, one task per line in
+    /// sorted order. Wont tasks are left unticked and struck through.
+    fn to_markdown(&self) -> Result<String, Self::Error> {
+        let mut markdown = String::new();
+        for task in self.sorted() {
+            let task = task?.projection();
+            let line = match task.status {
+                Status::Open => format!("- [ ] {}\n", task.name),
+                Status::Done => format!("- [x] {}\n", task.name),
+                Status::Wont => format!("- [ ] ~~{}~~\n", task.name),
+            };
+            markdown.push_str(&line);
+        }
+        Ok(markdown)
+    }
+
+    /// The list as CSV, with a header row and then one row per task
+    /// in enumerate order. Tags are joined with commas, and times are
+    /// in RFC 3339 format.
+    fn to_csv(&self) -> Result<String, Self::Error> {
+        let mut csv = String::from("id,status,name,due,priority,tags,notes,created,completed,depends_on,parent,recurrence\n");
+        for result in self.enumerate() {
+            let (id, task) = result?;
+            let task = task.projection();
+            let fields = [id.to_string(),
+                          String::from(task.status.name()),
+                          task.name,
+                          task.due.map(|due| due.to_string()).unwrap_or_default(),
+                          task.priority.to_string(),
+                          task.tags.join(","),
+                          task.notes,
+                          task.created.to_rfc3339(),
+                          task.completed.map(|completed| completed.to_rfc3339()).unwrap_or_default(),
+                          ids_field(&task.depends_on),
+                          task.parent.map(|parent| parent.to_string()).unwrap_or_default(),
+                          String::from(task.recurrence.as_ref().map_or("", Recurrence::name))];
+            let row = fields.iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<_>>();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        Ok(csv)
+    }
+
+    /// The list as an iCalendar VCALENDAR with one VTODO per task, in
+    /// enumerate order. Each VTODO's UID is the task's id, and tasks
+    /// without a due date have no DUE property.
+    fn to_icalendar(&self) -> Result<String, Self::Error> {
+        let stamp = ical_time(&Utc::now());
+        let mut lines = vec![String::from("BEGIN:VCALENDAR"),
+                             String::from("VERSION:2.0"),
+                             String::from("PRODID:-//ado//ado//EN")];
+        for result in self.enumerate() {
+            let (id, task) = result?;
+            let task = task.projection();
+            let status = match task.status {
+                Status::Open => "NEEDS-ACTION",
+                Status::Done => "COMPLETED",
+                Status::Wont => "CANCELLED",
+            };
+            lines.push(String::from("BEGIN:VTODO"));
+            lines.push(format!("UID:{}@ado", id));
+            lines.push(format!("DTSTAMP:{}", stamp));
+            lines.push(format!("CREATED:{}", ical_time(&task.created)));
+            lines.push(format!("SUMMARY:{}", ical_text(&task.name)));
+            lines.push(format!("STATUS:{}", status));
+            if let Some(due) = task.due {
+                lines.push(format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+            }
+            if let Some(completed) = task.completed {
+                lines.push(format!("COMPLETED:{}", ical_time(&completed)));
+            }
+            if !task.notes.is_empty() {
+                lines.push(format!("DESCRIPTION:{}", ical_text(&task.notes)));
+            }
+            lines.push(String::from("END:VTODO"));
+        }
+        lines.push(String::from("END:VCALENDAR"));
+
+        let mut calendar = String::new();
+        for line in lines {
+            calendar.push_str(&ical_fold(&line));
+        }
+        Ok(calendar)
+    }
+
+    /// Tasks ordered by descending priority, then by ascending id.
+    fn sorted_by_priority(&self) -> ResultIter<'_, &Self::Task, Self::Error> {
+        let mut pairs = match self.enumerate().collect::<Result<Vec<_>, _>>() {
+            Ok(pairs) => pairs,
+            Err(err) => return Box::new(::std::iter::once(Err(err))),
+        };
+        pairs.sort_by_key(|&(id, task)| (Reverse(task.projection().priority), id));
+        Box::new(pairs.into_iter()
+            .map(|(_, task)| Ok(task)))
+    }
+
+    /// Tasks ordered by status: Open, then Done, then Wont. Tasks with
+    /// the same status keep their enumerate order.
+    fn sorted_by_status(&self) -> ResultIter<'_, (usize, &Self::Task), Self::Error> {
+        let mut pairs = match self.enumerate().collect::<Result<Vec<_>, _>>() {
+            Ok(pairs) => pairs,
+            Err(err) => return Box::new(::std::iter::once(Err(err))),
+        };
+        pairs.sort_by_key(|&(_, task)| match task.view().status {
+            Status::Open => 0,
+            Status::Done => 1,
+            Status::Wont => 2,
+        });
+        Box::new(pairs.into_iter().map(Ok))
+    }
+
+    /// Tasks ordered by name, ignoring case. Tasks with the same name
+    /// keep their enumerate order.
+    ///
+    /// The whole list is collected and sorted before the first task is
+    /// returned, which allocates a `Vec` of every task and a lowercase
+    /// copy of each name.
+    fn sorted_by_name(&self) -> ResultIter<'_, (usize, &Self::Task), Self::Error> {
+        let mut pairs = match self.enumerate().collect::<Result<Vec<_>, _>>() {
+            Ok(pairs) => pairs,
+            Err(err) => return Box::new(::std::iter::once(Err(err))),
+        };
+        pairs.sort_by_cached_key(|&(_, task)| task.view().name.to_lowercase());
+        Box::new(pairs.into_iter().map(Ok))
+    }
+
+    /// Tasks whose names contain the query, ignoring case.
+    /// An empty query matches every task.
+    fn search(&self, query: &str) -> ResultIter<'_, (usize, &Self::Task), Self::Error> {
+        let query = query.to_lowercase();
+        let iter = self.enumerate()
+            .filter(move |result| match *result {
+                Ok((_, task)) => task.projection().name.to_lowercase().contains(&query),
+                Err(_) => true,
+            });
+        Box::new(iter)
+    }
+
+    /// The first task, in enumerate order, whose name is exactly `name`.
+    /// Later tasks with the same name can only be found by id.
+    fn find_by_name(&self, name: &str) -> Result<(usize, &Self::Task), Self::Error>
+        where Self::Error: From<Error>
+    {
+        for result in self.enumerate() {
+            let (id, task) = result?;
+            if task.view().name == name {
+                return Ok((id, task));
+            }
+        }
+        Err(Self::Error::from(Error::NoSuchTask))
+    }
+
+    /// Tasks with the given status, in enumerate order.
+    fn with_status(&self, status: Status) -> ResultIter<'_, (usize, &Self::Task), Self::Error> {
+        let iter = self.enumerate()
+            .filter(move |result| match *result {
+                Ok((_, task)) => task.projection().status == status,
+                Err(_) => true,
+            });
+        Box::new(iter)
+    }
+
+    /// Tasks which carry exactly the given tag, in enumerate order.
+    fn find_by_tag<'a>(&'a self, tag: &'a str) -> ResultIter<'a, &'a Self::Task, Self::Error> {
+        let iter = self.enumerate()
+            .map(|result| result.map(|(_, task)| task))
+            .filter(move |result| match *result {
+                Ok(task) => task.projection().tags.iter().any(|t| t == tag),
+                Err(_) => true,
+            });
+        Box::new(iter)
+    }
+
     fn find(&self, id: usize) -> Result<&Self::Task, Self::Error>;
+
+    /// Whether there is a task with the given id.
+    ///
+    /// Lists which read tasks lazily should override this so that the
+    /// task isn't read just to check for it.
+    fn exists(&self, id: usize) -> bool {
+        self.find(id).is_ok()
+    }
+
+    /// Whether every task has been read, so that going through them all
+    /// won't touch storage.
+    ///
+    /// Lists which read tasks lazily should override this.
+    fn is_loaded(&self) -> bool {
+        true
+    }
+
+    /// Whether any task the given one depends on isn't Done yet.
+    /// Dependencies which have been removed from the list are ignored.
+    fn is_blocked(&self, id: usize) -> Result<bool, Self::Error> {
+        let depends_on = self.find(id)?.projection().depends_on;
+        for result in self.ids() {
+            let other = result?;
+            if depends_on.contains(&other) && self.find(other)?.projection().status != Status::Done {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Moves a task directly to the given status, like
+    /// `Task::goto_status`, but refuses to finish a blocked task.
+    ///
+    /// Finishing a recurring task creates its next occurrence: an Open
+    /// copy due one period after it, or after today if it had no due
+    /// date. The finished task stops recurring, so only the newest
+    /// occurrence carries the rule.
+    fn goto_status(&mut self, id: usize, target: Status) -> Result<(), Self::Error>
+        where Self::Error: From<Error>
+    {
+        if target == Status::Done && self.is_blocked(id)? {
+            return Err(Self::Error::from(Error::Blocked(id)));
+        }
+        let previous = self.find(id)?.projection();
+        let finishing = target == Status::Done && previous.status != Status::Done;
+        self.find_mut(id)?.goto_status(target)?;
+
+        let recurrence = match previous.recurrence {
+            Some(ref recurrence) if finishing => {
+                recurrence.clone()
+            }
+            _ => return Ok(()),
+        };
+        self.find_mut(id)?.set_recurrence(None)?;
+        let next = self.create(&previous.name)?;
+        let task = self.find_mut(next)?;
+        let due = previous.due.unwrap_or_else(|| Utc::now().date_naive());
+        task.set_due(Some(recurrence.advance(due)))?;
+        task.set_priority(previous.priority)?;
+        for tag in previous.tags.iter() {
+            task.add_tag(tag)?;
+        }
+        task.set_notes(&previous.notes)?;
+        task.set_parent(previous.parent)?;
+        task.set_recurrence(Some(recurrence))?;
+        Ok(())
+    }
+
+    /// Moves every task with the status `from` to `to`, returning how
+    /// many were moved.
+    ///
+    /// Each task is moved with `Task::goto_status`, so unlike the list's
+    /// `goto_status` this doesn't refuse blocked tasks or repeat
+    /// recurring ones: the tasks are moved together, whatever depends on
+    /// what. Tasks moved before an error stay moved.
+    fn set_status_all(&mut self, from: Status, to: Status) -> Result<usize, Self::Error> {
+        let mut ids = Vec::new();
+        for result in self.enumerate() {
+            let (id, task) = result?;
+            if task.view().status == from {
+                ids.push(id);
+            }
+        }
+        for &id in ids.iter() {
+            self.find_mut(id)?.goto_status(to.clone())?;
+        }
+        Ok(ids.len())
+    }
+
+    /// Makes a task depend on another, so that it can't be finished
+    /// until the other is Done. A task can't depend on itself, even
+    /// through other tasks.
+    fn add_dependency(&mut self, id: usize, dependency: usize) -> Result<(), Self::Error>
+        where Self::Error: From<Error>
+    {
+        let mut depends_on = self.find(id)?.projection().depends_on;
+        if !self.exists(dependency) {
+            return Err(Self::Error::from(Error::NotFound(dependency)));
+        }
+        if depends_on.contains(&dependency) {
+            return Ok(());
+        }
+
+        let mut graph = HashMap::new();
+        for result in self.enumerate() {
+            let (other, task) = result?;
+            graph.insert(other, task.projection().depends_on);
+        }
+        let mut pending = vec![dependency];
+        let mut seen = Vec::new();
+        while let Some(next) = pending.pop() {
+            if next == id {
+                return Err(Self::Error::from(Error::DependencyCycle { id, dependency }));
+            }
+            if seen.contains(&next) {
+                continue;
+            }
+            seen.push(next);
+            if let Some(edges) = graph.get(&next) {
+                pending.extend(edges);
+            }
+        }
+
+        depends_on.push(dependency);
+        self.find_mut(id)?.set_depends_on(&depends_on)?;
+        Ok(())
+    }
+
+    /// The subtasks directly under the given task, in enumerate order.
+    fn children(&self, id: usize) -> ResultIter<'_, (usize, &Self::Task), Self::Error> {
+        let iter = self.enumerate()
+            .filter(move |result| match *result {
+                Ok((_, task)) => task.projection().parent == Some(id),
+                Err(_) => true,
+            });
+        Box::new(iter)
+    }
+
+    /// Moves a task under another as a subtask, or back to the top level
+    /// with `None`. A task can't end up under itself, even indirectly.
+    fn set_parent(&mut self, id: usize, parent: Option<usize>) -> Result<(), Self::Error>
+        where Self::Error: From<Error>
+    {
+        self.find(id)?;
+        let mut ancestor = parent;
+        while let Some(next) = ancestor {
+            if next == id {
+                return Err(Self::Error::from(Error::ParentCycle { id, parent: parent.unwrap() }));
+            }
+            ancestor = self.find(next)?.projection().parent;
+        }
+        self.find_mut(id)?.set_parent(parent)?;
+        Ok(())
+    }
+
     fn find_mut(&mut self, id: usize) -> Result<&mut Self::Task, Self::Error>;
     fn remove(&mut self, id: usize) -> Result<Self::Task, Self::Error>;
+
+    /// Removes every task from the list.
+    fn clear(&mut self) -> Result<(), Self::Error> {
+        let ids = self.ids().collect::<Result<Vec<_>, _>>()?;
+        for id in ids {
+            self.remove(id)?;
+        }
+        Ok(())
+    }
+
+    /// Removes every task for which `f` returns false.
+    fn retain<F>(&mut self, mut f: F) -> Result<(), Self::Error>
+        where F: FnMut(&Self::Task) -> bool
+    {
+        let mut removed = Vec::new();
+        for result in self.enumerate() {
+            let (id, task) = result?;
+            if !f(task) {
+                removed.push(id);
+            }
+        }
+        for id in removed {
+            self.remove(id)?;
+        }
+        Ok(())
+    }
 }
 
 pub trait Task {
@@ -61,50 +517,375 @@ pub trait Task {
     fn goto_next_status(&mut self) -> Result<(), Self::Error>;
     fn goto_next_back_status(&mut self) -> Result<(), Self::Error>;
 
+    /// Move directly to the given status.
+    /// Every jump is valid, including to the current status.
+    fn goto_status(&mut self, target: Status) -> Result<(), Self::Error>;
+
+    /// Like `goto_next_status`, but Done wraps around to Wont rather
+    /// than failing, so the cycle is Open, Done, Wont, Open.
+    /// Only errors from the underlying task (e.g. storage) are returned.
+    fn goto_next_status_wrapping(&mut self) -> Result<(), Self::Error> {
+        match self.projection().status {
+            Status::Done => self.goto_status(Status::Wont),
+            _ => self.goto_next_status(),
+        }
+    }
+
+    /// Fails with `EmptyName` if the name is blank, or `InvalidName` if
+    /// it contains a line break.
+    fn rename(&mut self, name: &str) -> Result<(), Self::Error>;
+
+    /// Set the priority; 0 means none, and higher is more urgent.
+    fn set_priority(&mut self, priority: u8) -> Result<(), Self::Error>;
+
+    /// Add a free-form tag such as `@home` or `#urgent`.
+    /// Tags which are empty or contain commas or line breaks are rejected.
+    fn add_tag(&mut self, tag: &str) -> Result<(), Self::Error>;
+
+    /// Replace the free-text notes, which may span multiple lines.
+    fn set_notes(&mut self, notes: &str) -> Result<(), Self::Error>;
+
+    /// Set or clear the due date.
+    fn set_due(&mut self, due: Option<NaiveDate>) -> Result<(), Self::Error>;
+
+    /// Replace the ids of the tasks which must be Done before this one.
+    /// Use `TodoList::add_dependency` to have cycles rejected.
+    fn set_depends_on(&mut self, ids: &[usize]) -> Result<(), Self::Error>;
+
+    /// Set or clear the id of the task this is a subtask of.
+    /// Use `TodoList::set_parent` to have cycles rejected.
+    fn set_parent(&mut self, parent: Option<usize>) -> Result<(), Self::Error>;
+
+    /// Set or clear how often the task repeats.
+    /// Use `TodoList::goto_status` to have finishing it repeat it.
+    fn set_recurrence(&mut self, recurrence: Option<Recurrence>) -> Result<(), Self::Error>;
+
     fn projection(&self) -> BasicTask;
+
+    /// The fields needed to show the task in a list. By default this
+    /// is made from a projection; tasks which own their fields should
+    /// lend them instead, to save cloning on every redraw.
+    fn view(&self) -> TaskView<'_> {
+        TaskView::from(self.projection())
+    }
 }
 
+/// The fields of a task shown in a list, borrowed from the task where
+/// it can lend them. Tasks which can't, such as ones behind a `RefCell`,
+/// give owned copies instead.
 #[derive(Clone)]
+pub struct TaskView<'a> {
+    pub status: Status,
+    pub name: Cow<'a, str>,
+    pub due: Option<NaiveDate>,
+    pub priority: u8,
+    pub tags: Cow<'a, [String]>,
+    pub parent: Option<usize>,
+}
+
+impl<'a> From<BasicTask> for TaskView<'a> {
+    fn from(task: BasicTask) -> TaskView<'a> {
+        TaskView {
+            status: task.status,
+            name: Cow::Owned(task.name),
+            due: task.due,
+            priority: task.priority,
+            tags: Cow::Owned(task.tags),
+            parent: task.parent,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct BasicTask {
     pub status: Status,
     pub name: String,
+    pub due: Option<NaiveDate>,
+    pub priority: u8,
+    pub tags: Vec<String>,
+    pub notes: String,
+    pub created: DateTime<Utc>,
+    /// When the task last became Done, if it is Done.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub completed: Option<DateTime<Utc>>,
+    /// The ids of the tasks which must be Done before this one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub depends_on: Vec<usize>,
+    /// The id of the task this is a subtask of, if any.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parent: Option<usize>,
+    /// How often the task repeats, if it does.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub recurrence: Option<Recurrence>,
+}
+
+/// Names can't be blank, and are kept on one line, so that they can't
+/// be split across lines of a task file.
+fn check_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        return Err(Error::EmptyName);
+    }
+    if name.contains(&['\n', '\r'][..]) {
+        return Err(Error::InvalidName(String::from(name)));
+    }
+    Ok(())
+}
+
+impl BasicTask {
+    /// A new open task with the given name, created now.
+    pub fn new(name: &str) -> BasicTask {
+        BasicTask {
+            status: Status::Open,
+            name: String::from(name),
+            due: None,
+            priority: 0,
+            tags: Vec::new(),
+            notes: String::new(),
+            created: Utc::now(),
+            completed: None,
+            depends_on: Vec::new(),
+            parent: None,
+            recurrence: None,
+        }
+    }
+
+    /// Like `new`, but fails with `EmptyName` if the name is blank or
+    /// `InvalidName` if it contains a line break, which lists should use
+    /// for names they are given.
+    pub fn named(name: &str) -> Result<BasicTask> {
+        check_name(name)?;
+        Ok(BasicTask::new(name))
+    }
+
+    /// Changes the status, stamping the completion time when the task
+    /// becomes Done and clearing it when the task stops being Done.
+    fn set_status(&mut self, status: Status) {
+        if status != Status::Done {
+            self.completed = None;
+        } else if self.status != Status::Done {
+            self.completed = Some(Utc::now());
+        }
+        self.status = status;
+    }
 }
 
 impl Task for BasicTask {
     type Error = Error;
 
     fn goto_next_status(&mut self) -> Result<()> {
-        self.status = match self.status {
+        let next = match self.status {
             Status::Wont => Status::Open,
             Status::Open => Status::Done,
             Status::Done => return Err(Error::AlreadyDone),
         };
+        self.set_status(next);
         Ok(())
     }
 
     fn goto_next_back_status(&mut self) -> Result<()> {
-        self.status = match self.status {
+        let next = match self.status {
             Status::Open => Status::Wont,
             Status::Done => Status::Open,
-            Status::Wont => return Err(Error::AlreadyDone),
+            Status::Wont => return Err(Error::AlreadyWont),
         };
+        self.set_status(next);
+        Ok(())
+    }
+
+    fn goto_status(&mut self, target: Status) -> Result<()> {
+        self.set_status(target);
+        Ok(())
+    }
+
+    fn rename(&mut self, name: &str) -> Result<()> {
+        check_name(name)?;
+        self.name = String::from(name);
+        Ok(())
+    }
+
+    fn set_priority(&mut self, priority: u8) -> Result<()> {
+        self.priority = priority;
+        Ok(())
+    }
+
+    fn add_tag(&mut self, tag: &str) -> Result<()> {
+        if tag.is_empty() || tag.contains(&[',', '\n', '\r'][..]) {
+            return Err(Error::InvalidTag(String::from(tag)));
+        }
+        if !self.tags.iter().any(|t| t == tag) {
+            self.tags.push(String::from(tag));
+        }
+        Ok(())
+    }
+
+    fn set_notes(&mut self, notes: &str) -> Result<()> {
+        self.notes = String::from(notes);
+        Ok(())
+    }
+
+    fn set_due(&mut self, due: Option<NaiveDate>) -> Result<()> {
+        self.due = due;
+        Ok(())
+    }
+
+    fn set_depends_on(&mut self, ids: &[usize]) -> Result<()> {
+        self.depends_on = ids.to_vec();
+        Ok(())
+    }
+
+    fn set_parent(&mut self, parent: Option<usize>) -> Result<()> {
+        self.parent = parent;
+        Ok(())
+    }
+
+    fn set_recurrence(&mut self, recurrence: Option<Recurrence>) -> Result<()> {
+        self.recurrence = recurrence;
         Ok(())
     }
 
     fn projection(&self) -> BasicTask {
         BasicTask { ..self.clone() }
     }
+
+    fn view(&self) -> TaskView<'_> {
+        TaskView {
+            status: self.status.clone(),
+            name: Cow::Borrowed(&self.name),
+            due: self.due,
+            priority: self.priority,
+            tags: Cow::Borrowed(&self.tags),
+            parent: self.parent,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Status {
     Open,
     Done,
     Wont,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Recurrence {
+    /// The lowercase name of the recurrence, as used when serializing.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Recurrence::Daily => "daily",
+            Recurrence::Weekly => "weekly",
+            Recurrence::Monthly => "monthly",
+        }
+    }
+
+    /// The date one period after `date`. Monthly recurrences from a day
+    /// which the next month doesn't have land on its last day instead.
+    pub fn advance(&self, date: NaiveDate) -> NaiveDate {
+        match *self {
+            Recurrence::Daily => date + Days::new(1),
+            Recurrence::Weekly => date + Days::new(7),
+            Recurrence::Monthly => date + Months::new(1),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = Error;
+
+    /// Parses a recurrence by its lowercase name.
+    fn from_str(source: &str) -> Result<Recurrence> {
+        match source {
+            "daily" => Ok(Recurrence::Daily),
+            "weekly" => Ok(Recurrence::Weekly),
+            "monthly" => Ok(Recurrence::Monthly),
+            _ => Err(Error::InvalidRecurrence(String::from(source))),
+        }
+    }
+}
+
+/// Ids separated by commas, e.g. `1,4`.
+fn ids_field(ids: &[usize]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break,
+/// doubling any quotes inside it.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+/// A UTC time in iCalendar's basic format, e.g. `20240131T120000Z`.
+fn ical_time(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes an iCalendar TEXT value.
+fn ical_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Terminates a content line with CRLF, folding it so that no line is
+/// longer than 75 bytes. Continuation lines start with a space.
+fn ical_fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+impl Status {
+    /// The lowercase name of the status, as used when serializing.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Status::Open => "open",
+            Status::Done => "done",
+            Status::Wont => "wont",
+        }
+    }
+
+    /// A short checkbox-like symbol for the status: `[ ]`, `[x]` or `---`.
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            Status::Open => "[ ]",
+            Status::Done => "[x]",
+            Status::Wont => "---",
+        }
+    }
+}
+
 pub struct FakeTodoList {
     tasks: VecMap<BasicTask>,
+    /// The ids of the tasks, in the order they are listed.
+    order: Vec<usize>,
     next_id: usize,
 }
 
@@ -112,9 +893,43 @@ impl FakeTodoList {
     pub fn new() -> FakeTodoList {
         FakeTodoList {
             tasks: VecMap::new(),
+            order: Vec::new(),
             next_id: 0,
         }
     }
+
+    /// Moves a task to the given position in the list, or to the end if
+    /// the position is past the end.
+    pub fn move_to(&mut self, id: usize, index: usize) -> Result<()> {
+        let from = self.order
+            .iter()
+            .position(|&other| other == id)
+            .ok_or(Error::NotFound(id))?;
+        self.order.remove(from);
+        let index = cmp::min(index, self.order.len());
+        self.order.insert(index, id);
+        Ok(())
+    }
+
+    /// A list seeded with a task for each name and status, given ids
+    /// from 0 in order, as if each had been created in turn.
+    pub fn with_tasks(tasks: Vec<(&str, Status)>) -> FakeTodoList {
+        let mut list = FakeTodoList::new();
+        for (name, status) in tasks {
+            let mut task = BasicTask::new(name);
+            task.set_status(status);
+            list.tasks.insert(list.next_id, task);
+            list.order.push(list.next_id);
+            list.next_id += 1;
+        }
+        list
+    }
+}
+
+impl Default for FakeTodoList {
+    fn default() -> FakeTodoList {
+        FakeTodoList::new()
+    }
 }
 
 impl TodoList for FakeTodoList {
@@ -122,101 +937,486 @@ impl TodoList for FakeTodoList {
     type Task = BasicTask;
 
     fn create(&mut self, name: &str) -> Result<usize, Error> {
+        let task = BasicTask::named(name)?;
         let id = self.next_id;
         self.next_id += 1;
 
-        let new_task = BasicTask {
-            status: Status::Open,
-            name: String::from(name),
-        };
-
-        self.tasks.insert(id, new_task);
+        self.tasks.insert(id, task);
+        self.order.push(id);
         Ok(id)
     }
 
-    fn enumerate(&self) -> ResultIter<(usize, &Self::Task)> {
-        Box::new(self.tasks
+    fn enumerate(&self) -> ResultIter<'_, (usize, &Self::Task)> {
+        Box::new(self.order
             .iter()
-            .map(|pair| Ok(pair)))
+            .map(move |&id| Ok((id, &self.tasks[id]))))
     }
 
     fn remove(&mut self, id: usize) -> Result<Self::Task> {
-        self.tasks
+        let task = self.tasks
             .remove(id)
-            .map_or(Err(Error::NoSuchTask), |task| Ok(task))
+            .ok_or(Error::NotFound(id))?;
+        self.order.retain(|&other| other != id);
+        Ok(task)
+    }
+
+    fn len(&self) -> Result<usize> {
+        Ok(self.tasks.len())
     }
 
     fn find(&self, id: usize) -> Result<&Self::Task> {
-        Ok(&self.tasks[id])
+        self.tasks.get(id).ok_or(Error::NotFound(id))
+    }
+
+    fn exists(&self, id: usize) -> bool {
+        self.tasks.contains_key(id)
     }
 
     fn find_mut(&mut self, id: usize) -> Result<&mut Self::Task> {
-        Ok(&mut self.tasks[id])
+        self.tasks.get_mut(id).ok_or(Error::NotFound(id))
     }
 
-    fn iter(&self) -> ResultIter<&Self::Task> {
-        let iter = self.tasks
+    fn iter(&self) -> ResultIter<'_, &Self::Task> {
+        let iter = self.order
             .iter()
-            .map(|(_, task)| Ok(task));
+            .map(move |&id| Ok(&self.tasks[id]));
         Box::new(iter)
     }
 
-    fn iter_mut(&mut self) -> ResultIter<&mut Self::Task> {
-        let iter = self.tasks
+    fn iter_mut(&mut self) -> ResultIter<'_, &mut Self::Task> {
+        let mut tasks = self.tasks
             .iter_mut()
-            .map(|(_, task)| Ok(task));
+            .collect::<VecMap<_>>();
+        let iter = self.order
+            .iter()
+            .map(move |&id| Ok(tasks.remove(id).unwrap()));
         Box::new(iter)
     }
 
     fn into_iter<'a>(self) -> ResultIter<'a, Self::Task> {
-        let iter = self.tasks
+        let mut tasks = self.tasks;
+        let iter = self.order
             .into_iter()
-            .map(|(_, task)| Ok(task));
+            .map(move |id| Ok(tasks.remove(id).unwrap()));
         Box::new(iter)
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{}", ::std::error::Error::description(self))
+        match *self {
+            Error::AlreadyDone => write!(f, "The task is already finished"),
+            Error::AlreadyWont => write!(f, "The task has already been closed"),
+            Error::Blocked(id) => write!(f, "Task {} depends on tasks which aren't done yet", id),
+            Error::DependencyCycle { id, dependency } => {
+                write!(f, "Task {} can't depend on task {}, which depends on it", id, dependency)
+            }
+            Error::DuplicateTask(id) => write!(f, "Task {} exists more than once", id),
+            Error::EmptyName => write!(f, "Tasks can't have an empty name"),
+            Error::MalformedTask { id, ref reason } => {
+                write!(f, "Task {} is malformed: {}", id, reason)
+            }
+            Error::NoSuchTask => write!(f, "No such task could be found"),
+            Error::NotFound(id) => write!(f, "Task {} could not be found", id),
+            Error::ParentCycle { id, parent } => {
+                write!(f, "Task {} can't be a subtask of task {}, which is under it", id, parent)
+            }
+            Error::InvalidName(ref name) => {
+                write!(f, "Invalid name {:?}: names can't contain line breaks", name)
+            }
+            Error::InvalidRecurrence(ref source) => {
+                write!(f, "Invalid recurrence {:?}: expected daily, weekly or monthly", source)
+            }
+            Error::InvalidStatus(ref source) => write!(f, "Invalid status string: {:?}", source),
+            Error::InvalidTag(ref tag) => {
+                write!(f,
+                       "Invalid tag {:?}: tags must be non-empty without commas or line breaks",
+                       tag)
+            }
+            Error::External(ref source) => write!(f, "An external error occured: {}", source),
+            Error::Io(ref source) => write!(f, "A file could not be read or written: {}", source),
+            Error::Locked => {
+                write!(f,
+                       "The list is in use by another instance; remove its .lock file if there \
+                        isn't one")
+            }
+        }
     }
 }
 
 impl ::std::error::Error for Error {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
-            Error::AlreadyDone => "The task is already finished",
-            Error::AlreadyWont => "The task has already been closed",
-            Error::NoSuchTask => "No such task could be found",
-            Error::External(_) => "An external error occured",
+            Error::External(ref source) => Some(&**source),
+            Error::Io(ref source) => Some(source),
+            _ => None,
         }
     }
 }
 
-impl<'a> From<&'a str> for Status {
-    fn from(source: &str) -> Status {
+impl Display for ParseStatusError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Invalid status string: {:?}", self.input)
+    }
+}
+
+impl ::std::error::Error for ParseStatusError {}
+
+impl FromStr for Status {
+    type Err = ParseStatusError;
+
+    fn from_str(source: &str) -> Result<Status, ParseStatusError> {
         match source {
-            "Open" => Status::Open,
-            "Done" => Status::Done,
-            "Wont" => Status::Wont,
-            _ => panic!("Invalid status string"),
+            "Open" => Ok(Status::Open),
+            "Done" => Ok(Status::Done),
+            "Wont" => Ok(Status::Wont),
+            _ => Err(ParseStatusError { input: String::from(source) }),
         }
     }
 }
 
 impl Display for Status {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match *self {
-            Status::Open => write!(f, "     [ ]      "),
-            Status::Done => write!(f, "           [x]"),
-            Status::Wont => write!(f, "----          "),
-        }
+        write!(f, "{}", self.symbol())
     }
 }
 
 impl Display for BasicTask {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let check = format!("{}", self.status);
-        write!(f, "{} {}", check, self.name)
+        write!(f, "{} {}", self.status, self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The ids of the tasks in a list of results, in order.
+    fn ids_of<'a, T: 'a>(results: ResultIter<'a, (usize, &'a T)>) -> Vec<usize> {
+        results.map(|result| result.unwrap().0).collect()
+    }
+
+    fn open(names: &[&str]) -> FakeTodoList {
+        FakeTodoList::with_tasks(names.iter().map(|&name| (name, Status::Open)).collect())
+    }
+
+    #[test]
+    fn search_with_an_empty_query_matches_every_task() {
+        let todo_list = open(&["apple", "banana"]);
+        assert_eq!(vec![0, 1], ids_of(todo_list.search("")));
+    }
+
+    #[test]
+    fn search_without_matches_is_empty() {
+        let todo_list = open(&["apple", "banana"]);
+        assert!(ids_of(todo_list.search("cherry")).is_empty());
+    }
+
+    #[test]
+    fn search_ignores_case() {
+        let todo_list = open(&["Apple pie", "banana", "APPLE jam"]);
+        assert_eq!(vec![0, 2], ids_of(todo_list.search("apple")));
+        assert_eq!(vec![0, 2], ids_of(todo_list.search("aPpLe")));
+    }
+
+    #[test]
+    fn with_status_gives_only_tasks_with_that_status_in_order() {
+        let todo_list = FakeTodoList::with_tasks(vec![("a", Status::Done),
+                                                      ("b", Status::Open),
+                                                      ("c", Status::Done),
+                                                      ("d", Status::Wont)]);
+        assert_eq!(vec![0, 2], ids_of(todo_list.with_status(Status::Done)));
+        assert_eq!(vec![1], ids_of(todo_list.with_status(Status::Open)));
+        assert_eq!(vec![3], ids_of(todo_list.with_status(Status::Wont)));
+
+        let todo_list = FakeTodoList::with_tasks(vec![("a", Status::Open)]);
+        assert!(ids_of(todo_list.with_status(Status::Wont)).is_empty());
+    }
+
+    #[test]
+    fn goto_next_back_status_from_wont_is_already_wont() {
+        let mut task = BasicTask::new("a");
+        task.goto_next_back_status().unwrap();
+        assert_eq!(Status::Wont, task.status);
+        assert!(matches!(task.goto_next_back_status(), Err(Error::AlreadyWont)));
+        assert_eq!(Status::Wont, task.status);
+    }
+
+    #[test]
+    fn goto_next_status_from_done_is_already_done() {
+        let mut task = BasicTask::new("a");
+        task.goto_next_status().unwrap();
+        assert_eq!(Status::Done, task.status);
+        assert!(matches!(task.goto_next_status(), Err(Error::AlreadyDone)));
+        assert_eq!(Status::Done, task.status);
+    }
+
+    #[test]
+    fn goto_status_jumps_to_any_status_including_the_current_one() {
+        let mut task = BasicTask::new("a");
+        for (from, to) in [(Status::Wont, Status::Done),
+                           (Status::Done, Status::Wont),
+                           (Status::Open, Status::Open)] {
+            task.goto_status(from).unwrap();
+            task.goto_status(to.clone()).unwrap();
+            assert_eq!(to, task.status);
+        }
+    }
+
+    #[test]
+    fn count_by_status_has_an_entry_for_every_status() {
+        let todo_list = FakeTodoList::with_tasks(vec![("a", Status::Done),
+                                                      ("b", Status::Open),
+                                                      ("c", Status::Done)]);
+        assert_eq!(3, todo_list.count().unwrap());
+        let counts = todo_list.count_by_status().unwrap();
+        assert_eq!(1, counts["open"]);
+        assert_eq!(2, counts["done"]);
+        assert_eq!(0, counts["wont"]);
+    }
+
+    #[test]
+    fn create_all_returns_the_ids_in_the_order_of_the_names() {
+        let mut todo_list = open(&["a"]);
+        assert_eq!(vec![1, 2], todo_list.create_all(&["b", "c"]).unwrap());
+        assert_eq!("c", todo_list.find(2).unwrap().name);
+        assert!(todo_list.create_all(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn completed_is_stamped_on_becoming_done_and_cleared_on_leaving_it() {
+        let mut task = BasicTask::new("a");
+        assert!(task.completed.is_none());
+        task.goto_status(Status::Done).unwrap();
+        let completed = task.completed.expect("Done tasks have a completion time");
+        assert!(completed >= task.created);
+
+        // Staying Done keeps the original time.
+        task.goto_status(Status::Done).unwrap();
+        assert_eq!(Some(completed), task.completed);
+
+        task.goto_next_back_status().unwrap();
+        assert!(task.completed.is_none());
+    }
+
+    #[test]
+    fn external_errors_keep_their_source() {
+        use std::error::Error as StdError;
+
+        let err = Error::from(::std::fmt::Error);
+        assert!(matches!(err, Error::External(_)));
+        let source = err.source().expect("External errors have a source");
+        assert!(source.is::<::std::fmt::Error>());
+        assert!(Error::NoSuchTask.source().is_none());
+
+        let cause = ::std::io::Error::other("the disk is on fire");
+        let err = Error::external(cause);
+        assert!(err.to_string().contains("the disk is on fire"), "{}", err);
+        let source = err.source()
+            .and_then(|source| source.downcast_ref::<::std::io::Error>())
+            .expect("External keeps the io::Error it wraps");
+        assert_eq!("the disk is on fire", source.to_string());
+    }
+
+    #[test]
+    fn to_markdown_ticks_done_tasks_and_strikes_through_wont_tasks() {
+        let todo_list = FakeTodoList::with_tasks(vec![("a", Status::Open),
+                                                      ("b", Status::Done),
+                                                      ("c", Status::Wont)]);
+        assert_eq!("- [ ] a\n- [x] b\n- [ ] ~~c~~\n", todo_list.to_markdown().unwrap());
+    }
+
+    #[test]
+    fn to_icalendar_writes_a_vtodo_per_task_with_due_dates_when_set() {
+        let mut todo_list = FakeTodoList::with_tasks(vec![("Buy milk, eggs", Status::Open),
+                                                          ("b", Status::Done)]);
+        todo_list.find_mut(0).unwrap().set_due(NaiveDate::from_ymd_opt(2024, 1, 31)).unwrap();
+        let calendar = todo_list.to_icalendar().unwrap();
+        let lines = calendar.split("\r\n").collect::<Vec<_>>();
+
+        assert_eq!("BEGIN:VCALENDAR", lines[0]);
+        assert_eq!(Some(&""), lines.last());
+        assert_eq!(2, lines.iter().filter(|&&line| line == "BEGIN:VTODO").count());
+        assert!(lines.contains(&"UID:0@ado"));
+        assert!(lines.contains(&"SUMMARY:Buy milk\\, eggs"));
+        assert!(lines.contains(&"DUE;VALUE=DATE:20240131"));
+        assert!(lines.contains(&"STATUS:COMPLETED"));
+        assert_eq!(1, lines.iter().filter(|line| line.starts_with("DUE")).count());
+
+        // RFC 5545 needs a DTSTAMP, in UTC, on every VTODO.
+        let stamps = lines.iter()
+            .filter_map(|line| line.strip_prefix("DTSTAMP:"))
+            .collect::<Vec<_>>();
+        assert_eq!(2, stamps.len());
+        for stamp in stamps {
+            assert!(::chrono::NaiveDateTime::parse_from_str(stamp, "%Y%m%dT%H%M%SZ").is_ok(),
+                    "{:?} isn't a UTC date-time",
+                    stamp);
+        }
+    }
+
+    #[test]
+    fn ical_fold_keeps_lines_to_75_bytes() {
+        let line = "x".repeat(100);
+        assert_eq!(format!("{}\r\n {}\r\n", &line[..75], &line[75..]), ical_fold(&line));
+    }
+
+    #[test]
+    fn finishing_a_recurring_task_creates_its_next_occurrence() {
+        let mut todo_list = open(&["water plants"]);
+        {
+            let task = todo_list.find_mut(0).unwrap();
+            task.set_due(NaiveDate::from_ymd_opt(2024, 1, 1)).unwrap();
+            task.set_recurrence(Some(Recurrence::Weekly)).unwrap();
+        }
+        todo_list.goto_status(0, Status::Done).unwrap();
+
+        let finished = todo_list.find(0).unwrap();
+        assert_eq!(Status::Done, finished.status);
+        assert!(finished.recurrence.is_none());
+        let next = todo_list.find(1).unwrap();
+        assert_eq!("water plants", next.name);
+        assert_eq!(Status::Open, next.status);
+        assert_eq!(NaiveDate::from_ymd_opt(2024, 1, 8), next.due);
+        assert_eq!(Some(Recurrence::Weekly), next.recurrence);
+
+        // Finishing it again doesn't repeat it twice.
+        todo_list.goto_status(0, Status::Done).unwrap();
+        assert_eq!(2, todo_list.len().unwrap());
+    }
+
+    #[test]
+    fn monthly_recurrences_land_on_the_last_day_of_shorter_months() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        assert_eq!(NaiveDate::from_ymd_opt(2024, 2, 29), Some(Recurrence::Monthly.advance(date)));
+        assert_eq!(NaiveDate::from_ymd_opt(2024, 2, 1), Some(Recurrence::Daily.advance(date)));
+    }
+
+    #[test]
+    fn retain_removes_the_tasks_the_predicate_rejects() {
+        let mut todo_list = FakeTodoList::with_tasks(vec![("a", Status::Done),
+                                                          ("b", Status::Open),
+                                                          ("c", Status::Done)]);
+        todo_list.retain(|task| task.status != Status::Done).unwrap();
+        assert_eq!(vec![1], ids_of(todo_list.enumerate()));
+    }
+
+    #[test]
+    fn with_tasks_gives_ids_from_0_and_carries_on_from_there() {
+        let mut todo_list = FakeTodoList::with_tasks(vec![("a", Status::Open), ("b", Status::Wont)]);
+        assert_eq!(vec![0, 1], ids_of(todo_list.enumerate()));
+        assert_eq!("b", todo_list.find(1).unwrap().name);
+        assert_eq!(Status::Wont, todo_list.find(1).unwrap().status);
+        assert_eq!(2, todo_list.create("c").unwrap());
+    }
+
+    #[test]
+    fn find_and_find_mut_of_a_missing_id_are_not_found() {
+        let mut todo_list = open(&["a"]);
+        assert!(matches!(todo_list.find(5), Err(Error::NotFound(5))));
+        assert!(matches!(todo_list.find_mut(5), Err(Error::NotFound(5))));
+        assert!(matches!(todo_list.remove(5), Err(Error::NotFound(5))));
+    }
+
+    #[test]
+    fn find_by_name_gives_the_first_exact_match() {
+        let todo_list = open(&["a", "ab", "a"]);
+        assert_eq!(0, todo_list.find_by_name("a").unwrap().0);
+        assert_eq!(1, todo_list.find_by_name("ab").unwrap().0);
+        assert!(matches!(todo_list.find_by_name("A"), Err(Error::NoSuchTask)));
+    }
+
+    #[test]
+    fn sorted_by_name_ignores_case_and_keeps_ties_in_order() {
+        let todo_list = open(&["b", "A", "a", "C"]);
+        assert_eq!(vec![1, 2, 0, 3], ids_of(todo_list.sorted_by_name()));
+    }
+
+    #[test]
+    fn set_status_all_moves_only_tasks_with_the_first_status() {
+        let mut todo_list = FakeTodoList::with_tasks(vec![("a", Status::Done),
+                                                          ("b", Status::Open),
+                                                          ("c", Status::Done)]);
+        assert_eq!(2, todo_list.set_status_all(Status::Done, Status::Wont).unwrap());
+        assert_eq!(vec![0, 2], ids_of(todo_list.with_status(Status::Wont)));
+        assert_eq!(vec![1], ids_of(todo_list.with_status(Status::Open)));
+        assert_eq!(0, todo_list.set_status_all(Status::Done, Status::Open).unwrap());
+    }
+
+    #[test]
+    fn exists_only_for_tasks_in_the_list() {
+        let mut todo_list = open(&["a", "b"]);
+        todo_list.remove(0).unwrap();
+        assert!(!todo_list.exists(0));
+        assert!(todo_list.exists(1));
+        assert!(!todo_list.exists(2));
+    }
+
+    #[test]
+    fn blank_names_are_refused() {
+        let mut todo_list = open(&["a"]);
+        assert!(matches!(todo_list.create(""), Err(Error::EmptyName)));
+        assert!(matches!(todo_list.create(" \t"), Err(Error::EmptyName)));
+        assert!(matches!(todo_list.create_all(&["b", ""]), Err(Error::EmptyName)));
+        assert!(matches!(todo_list.find_mut(0).unwrap().rename(" "), Err(Error::EmptyName)));
+        assert_eq!("a", todo_list.find(0).unwrap().name);
+    }
+
+    #[test]
+    fn names_with_line_breaks_are_refused() {
+        assert!(matches!(BasicTask::named("a\nb"), Err(Error::InvalidName(_))));
+        assert!(matches!(BasicTask::named("a\rb"), Err(Error::InvalidName(_))));
+        let mut task = BasicTask::named("a b").unwrap();
+        assert!(matches!(task.rename("c\r\nd"), Err(Error::InvalidName(_))));
+        assert_eq!("a b", task.name);
+    }
+
+    #[test]
+    fn goto_status_goes_from_done_back_to_open_in_one_step() {
+        let mut task = BasicTask::new("a");
+        task.goto_status(Status::Done).unwrap();
+        assert!(task.completed.is_some());
+
+        task.goto_status(Status::Open).unwrap();
+        assert_eq!(Status::Open, task.status);
+        assert!(task.completed.is_none());
+    }
+
+    #[test]
+    fn count_by_status_follows_changes_to_the_list() {
+        let mut todo_list = FakeTodoList::with_tasks(vec![("a", Status::Open)]);
+        let counts = |todo_list: &FakeTodoList| {
+            let counts = todo_list.count_by_status().unwrap();
+            (counts["open"], counts["done"], counts["wont"])
+        };
+
+        let id = todo_list.create("b").unwrap();
+        assert_eq!((2, 0, 0), counts(&todo_list));
+        todo_list.find_mut(id).unwrap().goto_status(Status::Wont).unwrap();
+        assert_eq!((1, 0, 1), counts(&todo_list));
+        todo_list.find_mut(0).unwrap().goto_status(Status::Done).unwrap();
+        assert_eq!((0, 1, 1), counts(&todo_list));
+        todo_list.remove(id).unwrap();
+        assert_eq!((0, 1, 0), counts(&todo_list));
+        assert_eq!(1, todo_list.count().unwrap());
+    }
+
+    #[test]
+    fn fake_iterators_all_follow_the_order() {
+        let mut todo_list = open(&["a", "b", "c"]);
+        todo_list.move_to(2, 0).unwrap();
+
+        let names = todo_list.iter()
+            .map(|task| task.unwrap().name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["c", "a", "b"], names);
+        let names = todo_list.iter_mut()
+            .map(|task| task.unwrap().name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["c", "a", "b"], names);
+        let names = TodoList::into_iter(todo_list)
+            .map(|task| task.unwrap().name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["c", "a", "b"], names);
     }
 }
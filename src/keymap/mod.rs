@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::mem;
+use std::path::Path;
+
+use super::{FrontError, FrontResult};
+
+/// Something the interface can do, which a `Keymap` binds keys to.
+///
+/// Commands are declared in the order the help screen lists them.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Command {
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    Left,
+    Right,
+    Complete,
+    Reopen,
+    MoveDown,
+    MoveUp,
+    /// Creates a task with the given name. Keys are bound to it with an
+    /// empty name, which `gui` replaces with one read from the user.
+    Create(String),
+    Rename,
+    Details,
+    Remove,
+    Visual,
+    Yank,
+    Paste,
+    Undo,
+    Reload,
+    ToggleDone,
+    CycleSort,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    Top,
+    Bottom,
+    Redraw,
+    Help,
+    Quit,
+    /// Selects the task at a 1-based position, from a count typed
+    /// before `Bottom`. It has no keys of its own.
+    Goto(usize),
+    /// Does nothing, such as when an unfinished command is abandoned.
+    NoOp,
+}
+
+/// Each command with its name in `keymap.toml` and its description on
+/// the help screen.
+const COMMANDS: &[(Command, &str, &str)] =
+    &[(Command::Down, "down", "Select next task"),
+      (Command::Up, "up", "Select previous task"),
+      (Command::PageDown, "page-down", "Select the task a page down"),
+      (Command::PageUp, "page-up", "Select the task a page up"),
+      (Command::Left, "left", "Move the current task to previous status"),
+      (Command::Right, "right", "Move the current task to next status"),
+      (Command::Complete, "complete", "Mark the current task as done"),
+      (Command::Reopen, "reopen", "Reopen the current task"),
+      (Command::MoveDown, "move-down", "Move the current task down the list"),
+      (Command::MoveUp, "move-up", "Move the current task up the list"),
+      (Command::Create(String::new()), "create", "Open a new task"),
+      (Command::Rename, "rename", "Rename the current task"),
+      (Command::Details, "details", "Show the details of the current task"),
+      (Command::Remove, "remove", "Delete the current task"),
+      (Command::Visual, "visual", "Select tasks to delete or complete together"),
+      (Command::Yank, "yank", "Copy the current task"),
+      (Command::Paste, "paste", "Paste a copy of the task below the current one"),
+      (Command::Undo, "undo", "Undo the last delete"),
+      (Command::Reload, "reload", "Reload tasks changed by other programs"),
+      (Command::ToggleDone, "toggle-done", "Hide or show done tasks"),
+      (Command::CycleSort, "sort", "Sort by list order, status or name"),
+      (Command::Search, "search", "Search for a task by name"),
+      (Command::NextMatch, "next-match", "Select the next match"),
+      (Command::PreviousMatch, "previous-match", "Select the previous match"),
+      (Command::Top, "top", "Select the first task"),
+      (Command::Bottom, "bottom", "Select the last task"),
+      (Command::Redraw, "redraw", "Redraw the screen"),
+      (Command::Help, "help", "Show this help"),
+      (Command::Quit, "quit", "Quit")];
+
+/// The bindings used when `keymap.toml` doesn't change them.
+const DEFAULT_KEYS: &[(&str, Command)] = &[("j", Command::Down),
+                                           ("k", Command::Up),
+                                           ("^f", Command::PageDown),
+                                           ("^b", Command::PageUp),
+                                           ("h", Command::Left),
+                                           ("l", Command::Right),
+                                           ("x", Command::Complete),
+                                           ("X", Command::Reopen),
+                                           ("J", Command::MoveDown),
+                                           ("K", Command::MoveUp),
+                                           ("o", Command::Create(String::new())),
+                                           ("r", Command::Rename),
+                                           ("i", Command::Details),
+                                           ("D", Command::Remove),
+                                           ("dd", Command::Remove),
+                                           ("v", Command::Visual),
+                                           ("yy", Command::Yank),
+                                           ("p", Command::Paste),
+                                           ("u", Command::Undo),
+                                           ("R", Command::Reload),
+                                           ("z", Command::ToggleDone),
+                                           ("s", Command::CycleSort),
+                                           ("/", Command::Search),
+                                           ("n", Command::NextMatch),
+                                           ("N", Command::PreviousMatch),
+                                           ("gg", Command::Top),
+                                           ("G", Command::Bottom),
+                                           ("^l", Command::Redraw),
+                                           ("?", Command::Help),
+                                           ("q", Command::Quit)];
+
+impl Command {
+    /// What the command does, as shown on the help screen.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Command::Goto(_) => "Select the task at position n",
+            Command::NoOp => "Do nothing",
+            _ => {
+                COMMANDS.iter()
+                    .find(|&(command, _, _)| mem::discriminant(command) == mem::discriminant(self))
+                    .map(|&(_, _, description)| description)
+                    .unwrap()
+            }
+        }
+    }
+
+    /// The command with the given name in `keymap.toml`, such as
+    /// `page-down`.
+    #[cfg(feature = "toml")]
+    pub fn from_name(name: &str) -> FrontResult<Command> {
+        COMMANDS.iter()
+            .find(|&&(_, other, _)| other == name)
+            .map(|(command, _, _)| command.clone())
+            .ok_or_else(|| FrontError::Keymap(format!("no command is called {:?}", name)))
+    }
+}
+
+/// Which command each key, or pair of keys such as `gg`, runs.
+///
+/// Digits can't be bound, as they are kept for counts such as the 5
+/// in `5G`.
+pub struct Keymap {
+    bindings: HashMap<String, Command>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let mut keymap = Keymap { bindings: HashMap::new() };
+        for &(keys, ref command) in DEFAULT_KEYS {
+            keymap.bind(keys, command.clone()).unwrap();
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// The default bindings, changed by `keymap.toml` in the given
+    /// directory if there is one.
+    ///
+    /// The file maps keys to command names, like `j = "down"`. Control
+    /// keys are written with a caret, like `"^f" = "page-down"`.
+    #[cfg(feature = "toml")]
+    pub fn load(dir: &Path) -> FrontResult<Keymap> {
+        let content = match ::std::fs::read_to_string(dir.join("keymap.toml")) {
+            Ok(content) => content,
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => {
+                return Ok(Keymap::default());
+            }
+            Err(err) => return Err(FrontError::from(::ado::Error::from(err))),
+        };
+        let table = ::toml::from_str::<::toml::Table>(&content)
+            .map_err(|err| FrontError::Keymap(err.to_string()))?;
+
+        let mut keymap = Keymap::default();
+        for (keys, name) in table {
+            let command = match name {
+                ::toml::Value::String(ref name) => Command::from_name(name)?,
+                _ => return Err(FrontError::Keymap(format!("{} isn't bound to a name", keys))),
+            };
+            keymap.bind(&keys, command)?;
+        }
+        Ok(keymap)
+    }
+
+    /// The default bindings, as reading `keymap.toml` needs the `toml`
+    /// feature. An error is returned if there is one, so that it isn't
+    /// silently ignored.
+    #[cfg(not(feature = "toml"))]
+    pub fn load(dir: &Path) -> FrontResult<Keymap> {
+        if dir.join("keymap.toml").exists() {
+            return Err(FrontError::Keymap(String::from("reading it needs --features toml")));
+        }
+        Ok(Keymap::default())
+    }
+
+    /// Binds one or two keys to a command, replacing any binding which
+    /// would stop them being reached: a single key can't both run a
+    /// command and start a pair.
+    fn bind(&mut self, keys: &str, command: Command) -> FrontResult<()> {
+        let keys = parse_keys(keys)?;
+        let first = keys.chars().next().unwrap();
+        self.bindings.retain(|other, _| {
+            let other_first = other.chars().next().unwrap();
+            other_first != first || (other.len() > 1 && keys.len() > 1)
+        });
+        self.bindings.insert(keys, command);
+        Ok(())
+    }
+
+    /// The command bound to the given keys, if any.
+    pub fn get(&self, keys: &str) -> Option<Command> {
+        self.bindings.get(keys).cloned()
+    }
+
+    /// Whether the key starts a pair, so that the next key is needed to
+    /// know the command.
+    pub fn is_prefix(&self, key: char) -> bool {
+        self.bindings
+            .keys()
+            .any(|keys| keys.len() > 1 && keys.starts_with(key))
+    }
+
+    /// Every binding, with the keys as they are written in
+    /// `keymap.toml`, in the order of the help screen.
+    pub fn bindings(&self) -> Vec<(String, Command)> {
+        let mut bindings = self.bindings
+            .iter()
+            .map(|(keys, command)| (display_keys(keys), command.clone()))
+            .collect::<Vec<_>>();
+        bindings.sort_by(|a, b| (&a.1, &a.0).cmp(&(&b.1, &b.0)));
+        bindings
+    }
+}
+
+/// Reads keys until they make a command, which is `None` if they don't
+/// match any binding.
+///
+/// `next_key` is given the keys read so far, so that it can show them,
+/// and returns `None` if it gives up waiting for the next one, which
+/// abandons the command. A count of digits before the keys for `Bottom`
+/// makes it `Goto` that position; other commands ignore the count.
+pub fn parse_key<F>(keymap: &Keymap, mut next_key: F) -> Option<Command>
+    where F: FnMut(&str) -> Option<char>
+{
+    let mut pending = String::new();
+    loop {
+        let key = match next_key(&pending) {
+            Some(key) => key,
+            None => return Some(Command::NoOp),
+        };
+        let count = pending.trim_end_matches(|c: char| !c.is_ascii_digit())
+            .parse::<usize>()
+            .ok();
+
+        let keys = match pending.chars().last() {
+            // The second key of a pair such as gg.
+            Some(first) if !first.is_ascii_digit() => format!("{}{}", first, key),
+            _ if key.is_ascii_digit() && (key != '0' || count.is_some()) => {
+                pending.push(key);
+                continue;
+            }
+            _ if keymap.is_prefix(key) => {
+                pending.push(key);
+                continue;
+            }
+            _ => key.to_string(),
+        };
+        return match (keymap.get(&keys), count) {
+            (Some(Command::Bottom), Some(position)) if position != 0 => Some(Command::Goto(position)),
+            (command, _) => command,
+        };
+    }
+}
+
+/// Reads keys as written in `keymap.toml`, where `^f` is Ctrl-f.
+fn parse_keys(text: &str) -> FrontResult<String> {
+    let mut keys = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '^' if chars.peek().is_some_and(char::is_ascii_alphabetic) => {
+                let letter = chars.next().unwrap().to_ascii_lowercase();
+                keys.push(char::from(letter as u8 & 0x1f));
+            }
+            c if c.is_ascii() => keys.push(c),
+            _ => return Err(FrontError::Keymap(format!("{:?} isn't an ASCII key", text))),
+        }
+    }
+    match keys.chars().count() {
+        1 | 2 => (),
+        _ => return Err(FrontError::Keymap(format!("{:?} isn't one or two keys", text))),
+    }
+    if keys.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(FrontError::Keymap(format!("{:?} starts with a digit", text)));
+    }
+    Ok(keys)
+}
+
+/// Writes keys as they are in `keymap.toml`, the reverse of `parse_keys`.
+fn display_keys(keys: &str) -> String {
+    keys.chars()
+        .map(|c| if (c as u32) < 0x20 {
+            format!("^{}", char::from(c as u8 | 0x60))
+        } else {
+            c.to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the keys with the default bindings, giving up like an
+    /// abandoned command once they run out.
+    fn parse(keys: &str) -> Option<Command> {
+        let mut keys = keys.chars();
+        parse_key(&Keymap::default(), |_| keys.next())
+    }
+
+    #[test]
+    fn single_keys_and_pairs_give_their_commands() {
+        assert_eq!(Some(Command::Down), parse("j"));
+        assert_eq!(Some(Command::PageDown), parse("\u{6}"));
+        assert_eq!(Some(Command::Remove), parse("D"));
+        assert_eq!(Some(Command::Remove), parse("dd"));
+        assert_eq!(Some(Command::Top), parse("gg"));
+        assert_eq!(Some(Command::Bottom), parse("G"));
+    }
+
+    #[test]
+    fn a_count_before_bottom_gives_goto() {
+        assert_eq!(Some(Command::Goto(12)), parse("12G"));
+        assert_eq!(Some(Command::Goto(10)), parse("10G"));
+        assert_eq!(Some(Command::Top), parse("3gg"));
+        assert_eq!(Some(Command::Down), parse("3j"));
+    }
+
+    #[test]
+    fn unbound_keys_give_none() {
+        assert_eq!(None, parse("w"));
+        assert_eq!(None, parse("gx"));
+        assert_eq!(None, parse("3w"));
+        // A leading 0 is a key rather than the start of a count.
+        assert_eq!(None, parse("0G"));
+    }
+
+    #[test]
+    fn running_out_of_keys_abandons_the_command() {
+        let mut seen = Vec::new();
+        let mut keys = "3g".chars();
+        let command = parse_key(&Keymap::default(), |pending| {
+            seen.push(String::from(pending));
+            keys.next()
+        });
+        assert_eq!(Some(Command::NoOp), command);
+        assert_eq!(vec!["", "3", "3g"], seen);
+    }
+}
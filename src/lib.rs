@@ -1,5 +1,9 @@
+extern crate chrono;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 extern crate vec_map;
 
 pub mod core;
 
-pub use core::{BasicTask, Error, Result, ResultIter, Status, Task, TodoList};
+pub use core::{BasicTask, Error, ParseStatusError, Recurrence, Result, ResultIter, Status, Task, TaskView, TodoList};
@@ -1,27 +1,74 @@
+extern crate chrono;
 extern crate ncurses;
 extern crate ado;
-
+#[cfg(any(feature = "json", feature = "toml"))]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "toml")]
+extern crate toml;
+
+mod cli;
+mod keymap;
+#[cfg(feature = "toml")]
+mod toml_list;
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use ncurses::CURSOR_VISIBILITY;
 
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
+use std::cell::OnceCell;
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
 use std::fs::File;
 use std::fs;
-use std::ffi::OsString;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use std::io::prelude::*;
 
-use ado::{BasicTask, Error, ResultIter, Status, Task, TodoList};
+use keymap::{parse_key, Command, Keymap};
+
+use ado::{BasicTask, Error, Recurrence, ResultIter, Status, Task, TaskView, TodoList};
+
+#[cfg(feature = "toml")]
+use toml_list::TomlTodoList;
+
+const PATH: &str = "./.ado/";
+
+/// How many deleted tasks can be brought back with undo.
+const UNDO_LIMIT: usize = 10;
+
+/// How long an unfinished command such as `d` waits for its next key.
+const PENDING_TIMEOUT_MS: i32 = 1000;
 
-const PATH: &'static str = "./.ado/";
+/// Whether deleting a task asks for confirmation first.
+const CONFIRM_DELETE: bool = true;
 
 type FrontResult<T> = ::std::result::Result<T, FrontError>;
 
 #[derive(Debug)]
 enum FrontError {
     NoSuchCommand,
+    NothingToUndo,
+    EmptyClipboard,
+    /// Tasks can't be moved while they are shown in this order.
+    Sorted(Sort),
+    NoSearch,
+    NoMatch(String),
+    Usage(&'static str),
+    /// `keymap.toml` couldn't be used, for this reason.
+    Keymap(String),
+    /// `ado check` found this many task files which couldn't be read.
+    Unreadable(usize),
+    /// `ado migrate` would overwrite a different task in this file.
+    #[cfg(feature = "json")]
+    Exists(PathBuf),
     Ado(Error),
 }
 
@@ -33,28 +80,240 @@ impl From<Error> for FrontError {
 
 impl Display for FrontError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{}", ::std::error::Error::description(self))
+        match *self {
+            FrontError::NoSuchCommand => write!(f, "Command not recognised"),
+            FrontError::NothingToUndo => write!(f, "Nothing to undo"),
+            FrontError::EmptyClipboard => write!(f, "Nothing has been copied"),
+            FrontError::Sorted(sort) => {
+                write!(f, "Tasks can't be moved while sorted by {}", sort.name())
+            }
+            FrontError::NoSearch => write!(f, "No previous search"),
+            FrontError::NoMatch(ref query) => write!(f, "No tasks match {:?}", query),
+            FrontError::Usage(usage) => write!(f, "Usage: {}", usage),
+            FrontError::Keymap(ref reason) => write!(f, "keymap.toml: {}", reason),
+            FrontError::Unreadable(count) => write!(f, "{} task files could not be read", count),
+            #[cfg(feature = "json")]
+            FrontError::Exists(ref file_name) => {
+                write!(f,
+                       "{} already exists; use --force to overwrite it",
+                       file_name.display())
+            }
+            FrontError::Ado(ref cause) => write!(f, "{}", cause),
+        }
     }
 }
 
 impl ::std::error::Error for FrontError {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
-            FrontError::NoSuchCommand => "Command not recognised",
-            FrontError::Ado(ref cause) => cause.description(),
+            FrontError::Ado(ref source) => Some(source),
+            _ => None,
         }
     }
 }
 
-/// Constructs the application and runs the GUI.
+/// Constructs the application and runs the command given on the
+/// command line, or the GUI if there isn't one.
+///
+/// Tasks are stored in the format named by `ADO_FORMAT`, which
+/// defaults to `lines`.
 fn main() {
-    let todo_list = FileTodoList::new().unwrap();
-    let mut task_picker = TaskPicker {
-        position: 0,
-        tasks: todo_list,
+    let args = ::std::env::args().skip(1).collect::<Vec<_>>();
+    #[cfg(feature = "json")]
+    {
+        if args.first().map(String::as_str) == Some("migrate") {
+            if let Err(err) = migrate(&args[1..]) {
+                eprintln!("{}", err);
+                ::std::process::exit(1);
+            }
+            return;
+        }
+    }
+    let format = ::std::env::var("ADO_FORMAT").unwrap_or_else(|_| String::from("lines"));
+    match format.as_str() {
+        "lines" => run(FileTodoList::<Lines>::new(), skipped_messages, &args),
+        #[cfg(feature = "json")]
+        "json" => run(JsonTodoList::new(), skipped_messages, &args),
+        #[cfg(feature = "toml")]
+        "toml" => run(TomlTodoList::new(), |_| Vec::new(), &args),
+        _ => {
+            eprintln!("Unknown storage format {:?}", format);
+            ::std::process::exit(1);
+        }
+    }
+}
+
+/// Runs a command over a freshly opened list.
+///
+/// Without arguments the GUI is run, after showing the messages
+/// about the list which `messages` gives. Otherwise the arguments name
+/// a command, and the process exits with status 1 if it fails.
+fn run<T, M>(todo_list: ado::Result<T>, messages: M, args: &[String])
+    where T: Reorder<Error = Error> + Reload + Import + Check + Archive,
+          T::Task: Task<Error = Error>,
+          M: FnOnce(&T) -> Vec<String>
+{
+    let mut todo_list = match todo_list {
+        Ok(todo_list) => todo_list,
+        Err(err) => {
+            eprintln!("Could not load tasks: {}", err);
+            ::std::process::exit(1);
+        }
+    };
+
+    if !args.is_empty() {
+        if let Err(err) = cli::command(&mut todo_list, args) {
+            eprintln!("{}", err);
+            // Exiting skips destructors, so release the list first.
+            drop(todo_list);
+            ::std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut messages = messages(&todo_list);
+    let mut task_picker = match TaskPicker::new(todo_list) {
+        Ok(task_picker) => task_picker,
+        Err(err) => {
+            eprintln!("Could not load tasks: {}", err);
+            ::std::process::exit(1);
+        }
+    };
+    let keymap = Keymap::load(&default_path()).unwrap_or_else(|err| {
+        messages.push(format!("Using the default keys: {}", err));
+        Keymap::default()
+    });
+    match load_picker_state(&default_path()) {
+        Ok(Some((sort, current))) => {
+            task_picker.sort = sort;
+            if let Err(err) = task_picker.refresh_view() {
+                messages.push(err.to_string());
+            }
+            if let Some(id) = current {
+                task_picker.select_id(id);
+            }
+        }
+        Ok(None) => (),
+        Err(err) => messages.push(format!("Could not restore the sort and cursor: {}", err)),
+    }
+    gui(&mut task_picker, &keymap, &messages);
+    let current = task_picker.current_id().ok();
+    if let Err(err) = save_picker_state(&default_path(), task_picker.sort, current) {
+        eprintln!("Could not save the sort and cursor: {}", err);
+    }
+}
+
+/// The file in the list's directory which keeps the picker's sort and
+/// the id of the task under the cursor between sessions, one per line.
+const PICKER_STATE: &str = "picker";
+
+/// The sort and cursor saved by `save_picker_state`, if they have been.
+/// An unknown sort is read as the list's own order.
+fn load_picker_state(path: &Path) -> ado::Result<Option<(Sort, Option<usize>)>> {
+    let content = match fs::read_to_string(path.join(PICKER_STATE)) {
+        Ok(content) => content,
+        Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::from(err)),
     };
+    let mut lines = content.lines();
+    let sort = lines.next().and_then(Sort::from_name).unwrap_or(Sort::List);
+    let current = lines.next().and_then(|line| line.parse().ok());
+    Ok(Some((sort, current)))
+}
+
+/// Saves the sort and the id of the task under the cursor, for
+/// `load_picker_state` to restore next time.
+fn save_picker_state(path: &Path, sort: Sort, current: Option<usize>) -> ado::Result<()> {
+    let mut content = format!("{}\n", sort.name());
+    if let Some(id) = current {
+        content.push_str(&format!("{}\n", id));
+    }
+    replace_file(&path.join(PICKER_STATE), |file| file.write_all(content.as_bytes()))?;
+    Ok(())
+}
+
+/// `ado migrate --to json [--force] [--remove]` copies every task from
+/// the line-based files to JSON files with the same ids, so that the
+/// list can be used with `ADO_FORMAT=json`.
+///
+/// Running it again is harmless: JSON files which already hold the same
+/// task are left alone, and nothing is written if any JSON file holds a
+/// different one, unless `--force` is given. With `--remove`, the old
+/// files are moved to the trash once every task has been copied.
+#[cfg(feature = "json")]
+fn migrate(args: &[String]) -> FrontResult<()> {
+    const USAGE: &str = "ado migrate --to json [--force] [--remove]";
+    let mut to = None;
+    let mut force = false;
+    let mut remove = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" => to = args.next().map(String::as_str),
+            "--force" => force = true,
+            "--remove" => remove = true,
+            _ => return Err(FrontError::Usage(USAGE)),
+        }
+    }
+    if to != Some("json") {
+        return Err(FrontError::Usage(USAGE));
+    }
+
+    let mut lines = FileTodoList::<Lines>::new()?;
+    lines.load_all()?;
+    for message in skipped_messages(&lines) {
+        eprintln!("{}", message);
+    }
+
+    // Check every file before writing any, so a refusal changes nothing.
+    let mut pending = Vec::new();
+    let mut current = 0;
+    for result in lines.enumerate() {
+        let (id, task) = result?;
+        let task = task.projection();
+        let file_name = lines.path.join(format!("{:05}{}", id, Json::EXTENSION));
+        let content = ::serde_json::to_string_pretty(&task)
+            .map_err(Error::external)?;
+        match fs::read_to_string(&file_name) {
+            Ok(ref existing) if *existing == content => current += 1,
+            Ok(_) if !force => return Err(FrontError::Exists(file_name)),
+            Ok(_) => pending.push((task, file_name)),
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => {
+                pending.push((task, file_name))
+            }
+            Err(err) => return Err(FrontError::Ado(Error::from(err))),
+        }
+    }
+
+    let migrated = pending.len();
+    for (task, file_name) in pending {
+        FileTask::<BasicTask, Json>::new(task, file_name).map_err(Error::from)?;
+    }
+    if remove {
+        lines.clear()?;
+    }
+    println!("Migrated {} tasks to JSON, {} were already up to date", migrated, current);
+    Ok(())
+}
+
+/// A message for each malformed task file which `load_all` dropped,
+/// and each file which was skipped because it isn't a task file.
+///
+/// Nothing is read, so before `load_all` only the files which aren't
+/// task files are reported, and malformed ones are reported when they
+/// are first used instead.
+fn skipped_messages<F: Format>(todo_list: &FileTodoList<F>) -> Vec<String> {
+    todo_list.load_errors()
+        .iter()
+        .map(|err| format!("Skipped: {}", err))
+        .chain(todo_list.skipped_files().iter().map(|name| not_a_task_file(name)))
+        .collect()
+}
 
-    gui(&mut task_picker);
+/// The message for a file in a list's directory which was skipped
+/// because it isn't a task file.
+fn not_a_task_file(name: &str) -> String {
+    format!("Skipped {:?}: not a task file", name)
 }
 
 /// Handles input and output for the lifetime of the application.
@@ -66,85 +325,141 @@ fn main() {
 ///
 /// This function returns when the user enters a quit command.
 ///
+/// Any startup messages are shown beneath the list until the first
+/// key is pressed.
+///
 /// Partially completed commands are shown, but not complete
 /// or invalid commands.
 /// e.g. pressing 'd' will cause d to be printed at the bottom
 /// of the screen until the command is completed (e.g. as 'dd')
-/// or abandoned.
-fn gui<T>(task_picker: &mut TaskPicker<T>)
-    where T: TodoList<Error = Error>,
+/// or abandoned. A command is abandoned by an invalid key, or by
+/// `PENDING_TIMEOUT_MS` passing before the next key.
+///
+/// Each time round the loop, keys are parsed into a command through
+/// the keymap, the command is run, and the screen is drawn again.
+fn gui<T>(task_picker: &mut TaskPicker<T>, keymap: &Keymap, messages: &[String])
+    where T: Reorder<Error = Error> + Reload,
           T::Error: From<<T::Task as Task>::Error>,
           FrontError: From<<T::Task as Task>::Error>
 {
-    use ::std::error::Error;
-
+    // Keypad mode, which the mouse needs, waits ESCDELAY milliseconds
+    // after Esc in case it starts a key sequence. The default of a
+    // second would make Esc slow to cancel.
+    if ::std::env::var_os("ESCDELAY").is_none() {
+        ::std::env::set_var("ESCDELAY", "25");
+    }
     ::ncurses::initscr();
     ::ncurses::curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
     ::ncurses::noraw();
     ::ncurses::cbreak();
+    init_colours();
+    init_mouse();
 
     // Print the initial state of the task picker.
     ::ncurses::clear();
-    ::ncurses::printw(&format!("{}\n", task_picker));
+    render(task_picker);
+    for message in messages {
+        ::ncurses::printw(&format!("{}\n", message));
+    }
     ::ncurses::refresh();
 
     loop {
-        // Handle user input, and store any errors which are produced.
-        // Generate a new error if the input is unrecognised.
-        let result = match ::ncurses::getch() {
-            x => {
-                match char::from(x as u8) {
-                    // Quit on q.
-                    'q' => break,
-
-                    // Basic movement commands.
-                    'h' => task_picker.left(),
-                    'j' => task_picker.down(),
-                    'k' => task_picker.up(),
-                    'l' => task_picker.right(),
-
-                    // Get a new task name from the user and use the
-                    // name to create a new task.
-                    'o' => {
-                        ::ncurses::printw("\nEnter a task summary:\n");
-                        ::ncurses::curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
-                        ::ncurses::nocbreak();
-                        let mut name = String::new();
-                        ::ncurses::getstr(&mut name);
-                        ::ncurses::curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
-                        ::ncurses::cbreak();
-                        task_picker.create(name).map(|_| ())
+        // Read keys until they make a command, showing an unfinished
+        // one and waiting for the rest of it only for so long.
+        let mut clicked = false;
+        let command = parse_key(keymap, |pending| {
+            if !pending.is_empty() {
+                continue_with(task_picker, pending);
+            }
+            loop {
+                ::ncurses::timeout(if pending.is_empty() { -1 } else { PENDING_TIMEOUT_MS });
+                let code = ::ncurses::getch();
+                ::ncurses::timeout(-1);
+                match code {
+                    ::ncurses::ERR => return None,
+                    // Fit the list to the new size straight away, rather
+                    // than after the next key.
+                    ::ncurses::KEY_RESIZE => continue_with(task_picker, pending),
+                    // A click abandons any unfinished command, and is
+                    // handled once parse_key returns.
+                    ::ncurses::KEY_MOUSE => {
+                        clicked = true;
+                        return None;
                     }
+                    // Other special keys, such as the arrow keys, aren't bound.
+                    code if code > 0xff => return None,
+                    _ => return Some(char::from(code as u8)),
+                }
+            }
+        });
 
-                    // Long distance scrolling.
-                    'G' => task_picker.bottom(),
-                    'g' => {
-                        match char::from(::ncurses::getch() as u8) {
-                            'g' => task_picker.top(),
-                            _ => Err(FrontError::NoSuchCommand),
-                        }
-                    }
+        // Get a new task name from the user to create the task with.
+        let command = match command {
+            Some(Command::Create(_)) => Some(Command::Create(prompt("Enter a task summary:"))),
+            command => command,
+        };
 
-                    // Task deletion.
-                    'D' => task_picker.remove(),
-                    'd' => {
-                        match char::from(::ncurses::getch() as u8) {
-                            'd' => task_picker.remove(),
-                            _ => Err(FrontError::NoSuchCommand),
-                        }
-                    }
+        // Commands which prompt or draw are run here, and the rest only
+        // change the task picker.
+        let mut skipped = Vec::new();
+        let result = match command {
+            _ if clicked => click(task_picker),
+            Some(Command::Quit) => break,
+
+            // Paging by a screen at a time.
+            Some(Command::PageDown) => task_picker.page_down(page_height()),
+            Some(Command::PageUp) => task_picker.page_up(page_height()),
+
+            // Let the user edit the name of the current task.
+            Some(Command::Rename) => {
+                task_picker.current()
+                    .map(|task| task.projection().name)
+                    .and_then(|name| {
+                        let name = prompt_with("Edit the task summary:", &name);
+                        task_picker.rename(&name)
+                    })
+            }
 
-                    _ => Err(FrontError::NoSuchCommand),
-                }
+            // Show the details of the current task until a key is pressed.
+            Some(Command::Details) => {
+                task_picker.current()
+                    .map(|task| show_details(&task.projection()))
+            }
+
+            // Files skipped by the reload are listed beneath the list.
+            Some(Command::Reload) => task_picker.reload().map(|messages| skipped = messages),
+
+            Some(Command::Search) => search(task_picker),
+            Some(Command::Remove) => confirm_remove(task_picker),
+            Some(Command::Visual) => visual(task_picker, keymap),
+
+            // Draw the whole screen again on the next refresh, in case
+            // it has been garbled.
+            Some(Command::Redraw) => {
+                ::ncurses::clear();
+                Ok(())
             }
+
+            // List the key bindings until a key is pressed.
+            Some(Command::Help) => {
+                show_help(keymap);
+                Ok(())
+            }
+
+            Some(command) => handle_command(task_picker, command),
+            None => Err(FrontError::NoSuchCommand),
         };
 
         // Print the state of the task picker as well printing
-        // any required error messages.
-        ::ncurses::clear();
-        ::ncurses::printw(&format!("{}\n", task_picker));
+        // any required error messages. Erasing rather than clearing
+        // lets refresh send only the rows which changed.
+        ::ncurses::erase();
+        render(task_picker);
         if let Err(err) = result {
-            ::ncurses::printw(&format!("{}\n", err.description()));
+            ::ncurses::printw(&format!("{}\n", err));
+        }
+        for message in skipped {
+            ::ncurses::printw(&format!("{}\n", message));
         }
         ::ncurses::refresh();
     }
@@ -152,321 +467,3454 @@ fn gui<T>(task_picker: &mut TaskPicker<T>)
     ::ncurses::endwin();
 }
 
-struct TaskPicker<T> {
-    position: usize,
-    tasks: T,
+/// Reports clicks and double-clicks of the left button as `KEY_MOUSE`.
+fn init_mouse() {
+    ::ncurses::keypad(::ncurses::stdscr(), true);
+    let mask = ::ncurses::BUTTON1_CLICKED | ::ncurses::BUTTON1_DOUBLE_CLICKED;
+    ::ncurses::mousemask(mask as ::ncurses::mmask_t, None);
 }
 
-impl<T> TaskPicker<T>
-    where T: TodoList,
-          FrontError: From<T::Error>,
-          FrontError: From<<T::Task as Task>::Error>,
-          ado::Error: From<T::Error>,
-          ado::Error: From<<T::Task as Task>::Error>
+/// Selects the clicked task, and moves it to its next status if it was
+/// double-clicked. Clicks outside the list are ignored.
+fn click<T>(task_picker: &mut TaskPicker<T>) -> FrontResult<()>
+    where T: TodoList<Error = Error>,
+          T::Error: From<<T::Task as Task>::Error>,
+          FrontError: From<<T::Task as Task>::Error>
 {
-    fn top(&mut self) -> FrontResult<()> {
-        self.position = 0;
-        Ok(())
+    let mut event = ::ncurses::MEVENT {
+        id: 0,
+        x: 0,
+        y: 0,
+        z: 0,
+        bstate: 0,
+    };
+    if ::ncurses::getmouse(&mut event) != ::ncurses::OK || event.y < LIST_TOP {
+        return Ok(());
     }
-
-    fn bottom(&mut self) -> FrontResult<()> {
-        let len = self.len()?;
-        if len == 0 {
-            Err(Error::NoSuchTask)?
-        } else {
-            self.position = len - 1;
-            Ok(())
-        }
+    let line = (event.y - LIST_TOP) as usize;
+    if !task_picker.select_line(line, page_height())? {
+        return Ok(());
     }
-
-    fn down(&mut self) -> FrontResult<()> {
-        let len = self.len()?;
-        if len != 0 && len - 1 != self.position {
-            self.position += 1;
-        }
-        Ok(())
+    if event.bstate & ::ncurses::BUTTON1_DOUBLE_CLICKED as ::ncurses::mmask_t != 0 {
+        task_picker.right()?;
     }
+    Ok(())
+}
 
-    fn len(&self) -> FrontResult<usize> {
-        Ok(self.tasks.ids().collect::<Vec<_>>().len())
-    }
+/// Reads a search query from the bottom line of the screen, moving the
+/// cursor to the first match after every key.
+///
+/// Enter keeps the cursor on the match and escape puts it back.
+fn search<T>(task_picker: &mut TaskPicker<T>) -> FrontResult<()>
+    where T: TodoList<Error = Error>,
+          T::Error: From<<T::Task as Task>::Error>,
+          FrontError: From<<T::Task as Task>::Error>
+{
+    let start = task_picker.position;
+    let mut query = String::new();
+    let mut result = Ok(());
+    loop {
+        ::ncurses::clear();
+        render(task_picker);
+        let bottom = ::ncurses::getmaxy(::ncurses::stdscr()) - 1;
+        ::ncurses::mv(bottom, 0);
+        match result {
+            Err(FrontError::NoMatch(_)) => ::ncurses::printw(&format!("/{} (no match)", query)),
+            _ => ::ncurses::printw(&format!("/{}", query)),
+        };
+        ::ncurses::refresh();
 
-    fn up(&mut self) -> FrontResult<()> {
-        if self.position != 0 {
-            self.position -= 1;
+        match ::ncurses::getch() {
+            10 | 13 | ::ncurses::KEY_ENTER => return result,
+            27 => {
+                task_picker.position = start;
+                return Ok(());
+            }
+            8 | 127 | ::ncurses::KEY_BACKSPACE => {
+                query.pop();
+            }
+            key if (32..127).contains(&key) => query.push(char::from(key as u8)),
+            _ => continue,
         }
-        Ok(())
-    }
 
-    fn right(&mut self) -> FrontResult<()> {
-        let id = self.current_id()?;
-        self.tasks
-            .find_mut(id)?
-            .goto_next_status()
-            .map_err(FrontError::from)
+        task_picker.position = start;
+        result = if query.is_empty() {
+            Ok(())
+        } else {
+            task_picker.search(&query)
+        };
     }
+}
 
-    fn left(&mut self) -> FrontResult<()> {
-        let id = self.current_id()?;
-        self.tasks
-            .find_mut(id)?
-            .goto_next_back_status()
-            .map_err(FrontError::from)
-    }
+/// Selects tasks from the current one as the cursor is moved with the
+/// keys for `Down` and `Up`, until those for `Remove` delete them, those
+/// for `Complete` complete them, or escape or those for `Visual` go back
+/// to selecting a single task.
+fn visual<T>(task_picker: &mut TaskPicker<T>, keymap: &Keymap) -> FrontResult<()>
+    where T: TodoList<Error = Error>,
+          T::Error: From<<T::Task as Task>::Error>,
+          FrontError: From<<T::Task as Task>::Error>
+{
+    task_picker.select()?;
+    let mut result = Ok(());
+    loop {
+        ::ncurses::erase();
+        render(task_picker);
+        let bottom = ::ncurses::getmaxy(::ncurses::stdscr()) - 1;
+        ::ncurses::mv(bottom, 0);
+        ::ncurses::clrtoeol();
+        match result {
+            Err(ref err) => ::ncurses::printw(&format!("-- VISUAL -- {}", err)),
+            Ok(()) => ::ncurses::printw("-- VISUAL --"),
+        };
+        ::ncurses::refresh();
 
-    fn current_id(&self) -> FrontResult<usize> {
-        self.tasks
-            .ids()
-            .nth(self.position)
-            .map(|result| result.map_err(Error::from))
-            .unwrap_or(Err(Error::NoSuchTask))
-            .map_err(FrontError::from)
+        let mut escaped = false;
+        let command = parse_key(keymap, |_| match ::ncurses::getch() {
+            0x1b => {
+                escaped = true;
+                None
+            }
+            code if code > 0xff => None,
+            code => Some(char::from(code as u8)),
+        });
+
+        let previous = task_picker.position;
+        result = match command {
+            _ if escaped => {
+                task_picker.selection = None;
+                return Ok(());
+            }
+            Some(Command::Visual) => {
+                task_picker.selection = None;
+                return Ok(());
+            }
+            Some(Command::Down) => task_picker.down(),
+            Some(Command::Up) => task_picker.up(),
+            Some(Command::Remove) => return confirm_remove_selected(task_picker),
+            Some(Command::Complete) => return task_picker.complete_selected(),
+            Some(Command::NoOp) => Ok(()),
+            _ => Err(FrontError::NoSuchCommand),
+        };
+        task_picker.extend_selection(previous);
     }
+}
 
-    fn create(&mut self, name: String) -> FrontResult<usize> {
-        let new_id = self.tasks.create(&name)?;
-        let mut new_position = self.position;
-        for (p, id) in self.tasks.ids().enumerate() {
-            match id {
-                Ok(id) if id == new_id => new_position = p,
-                _ => (),
-            };
+/// Removes the selected tasks, first asking the user to confirm it
+/// unless `CONFIRM_DELETE` is turned off. The selection ends either way.
+fn confirm_remove_selected<T>(task_picker: &mut TaskPicker<T>) -> FrontResult<()>
+    where T: TodoList<Error = Error>,
+          T::Error: From<<T::Task as Task>::Error>,
+          FrontError: From<<T::Task as Task>::Error>
+{
+    if CONFIRM_DELETE {
+        let count = task_picker.selection.clone().map_or(0, |selection| selection.len());
+        if !confirm(&format!("Delete {} tasks? (y/n)", count)) {
+            task_picker.selection = None;
+            return Ok(());
         }
-        self.position = new_position;
-        Ok(new_id)
     }
+    task_picker.remove_selected()
+}
 
-    fn remove(&mut self) -> FrontResult<()> {
-        let id = self.tasks
-            .ids()
-            .nth(self.position)
-            .map(|result| result.map_err(Error::from))
-            .unwrap_or(Err(Error::NoSuchTask))?;
+/// Runs a command which only changes the task picker, without drawing
+/// or reading input. Commands which need the screen, like `Create`
+/// with its prompt, aren't handled here and give `NoSuchCommand`.
+fn handle_command<T>(task_picker: &mut TaskPicker<T>, command: Command) -> FrontResult<()>
+    where T: Reorder<Error = Error> + Reload,
+          T::Error: From<<T::Task as Task>::Error>,
+          FrontError: From<<T::Task as Task>::Error>
+{
+    match command {
+        // Basic movement commands.
+        Command::Left => task_picker.left(),
+        Command::Down => task_picker.down(),
+        Command::Up => task_picker.up(),
+        Command::Right => task_picker.right(),
 
-        try!(self.tasks.remove(id));
+        // Jumping straight to Done or back to Open.
+        Command::Complete => task_picker.complete(),
+        Command::Reopen => task_picker.reopen(),
 
-        // Make sure we will still have our cursor in a valid position.
-        self.position = cmp::min(self.position, cmp::max(1, self.len()?) - 1);
-        Ok(())
+        // Reordering commands, which carry the task with the cursor.
+        Command::MoveDown => task_picker.move_down(),
+        Command::MoveUp => task_picker.move_up(),
+
+        Command::Create(name) => task_picker.create(name).map(|_| ()),
+
+        // Repeating the last search.
+        Command::NextMatch => task_picker.next_match(),
+        Command::PreviousMatch => task_picker.previous_match(),
+
+        // Long distance scrolling.
+        Command::Top => task_picker.top(),
+        Command::Bottom => task_picker.bottom(),
+        Command::Goto(position) => task_picker.goto_position(position),
+
+        // Copying a task and pasting it below the cursor.
+        Command::Yank => task_picker.yank(),
+        Command::Paste => task_picker.paste(),
+
+        // Bring back the most recently deleted task.
+        Command::Undo => task_picker.undo(),
+
+        // Pick up changes made to the list by other programs.
+        Command::Reload => task_picker.reload().map(|_| ()),
+
+        // Focusing on what is left to do.
+        Command::ToggleDone => task_picker.toggle_done(),
+        Command::CycleSort => task_picker.cycle_sort(),
+
+        Command::NoOp => Ok(()),
+
+        _ => Err(FrontError::NoSuchCommand),
     }
 }
 
-impl<T> Display for TaskPicker<T>
+/// Redraws the list with the keys of an unfinished command.
+fn continue_with<T>(task_picker: &mut TaskPicker<T>, keys: &str)
     where T: TodoList
 {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let mut strings = Vec::new();
+    ::ncurses::erase();
+    render(task_picker);
+    show_pending(keys);
+    ::ncurses::refresh();
+}
 
-        // TODO report errors instead of flat_mapping.
-        for (position, task) in self.tasks.sorted().enumerate() {
-            let task = task.map_err(|_| fmt::Error)?;
-            let marker = if position == self.position { ">" } else { " " };
-            strings.push(format!("{} {}", marker, task.projection()));
-        }
+/// Shows the keys of an unfinished command at the right of the bottom
+/// line, leaving the cursor where it was.
+fn show_pending(keys: &str) {
+    if keys.is_empty() {
+        return;
+    }
+    let y = ::ncurses::getcury(::ncurses::stdscr());
+    let x = ::ncurses::getcurx(::ncurses::stdscr());
+    let bottom = ::ncurses::getmaxy(::ncurses::stdscr()) - 1;
+    let column = ::ncurses::getmaxx(::ncurses::stdscr()) - 1 - keys.len() as i32;
+    ::ncurses::mvprintw(bottom, cmp::max(0, column), keys);
+    ::ncurses::mv(y, x);
+}
 
-        write!(f, "  WONT TODO DONE\n{}", strings.join("\n"))
+/// Removes the current task, first asking the user to confirm it
+/// unless `CONFIRM_DELETE` is turned off.
+fn confirm_remove<T>(task_picker: &mut TaskPicker<T>) -> FrontResult<()>
+    where T: TodoList<Error = Error>,
+          T::Error: From<<T::Task as Task>::Error>,
+          FrontError: From<<T::Task as Task>::Error>
+{
+    if CONFIRM_DELETE {
+        let name = task_picker.current()?.projection().name;
+        if !confirm(&format!("Delete '{}'? (y/n)", name)) {
+            return Ok(());
+        }
     }
+    task_picker.remove()
 }
 
-/// A Task source backed by flat files.
-pub struct FileTodoList {
-    cache: HashMap<usize, FileTask<BasicTask>>,
-    ids: Vec<usize>,
+/// Asks a yes/no question on the bottom line of the screen,
+/// returning whether the user answered yes.
+fn confirm(question: &str) -> bool {
+    let bottom = ::ncurses::getmaxy(::ncurses::stdscr()) - 1;
+    ::ncurses::mv(bottom, 0);
+    ::ncurses::clrtoeol();
+    ::ncurses::printw(question);
+    ::ncurses::curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+    ::ncurses::refresh();
+    let answer = ::ncurses::getch();
+    ::ncurses::curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+    answer == i32::from(b'y')
 }
 
-pub struct FileTask<T = BasicTask> {
-    file_name: String,
-    inner: T,
+/// Prints a message and reads a line of input from the user,
+/// showing the cursor while they type.
+fn prompt(message: &str) -> String {
+    ::ncurses::printw(&format!("\n{}\n", message));
+    ::ncurses::curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+    ::ncurses::nocbreak();
+    let mut input = String::new();
+    ::ncurses::getstr(&mut input);
+    ::ncurses::curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+    ::ncurses::cbreak();
+    input
 }
 
-impl<T> FileTask<T>
-    where T: Task,
-          Error: From<T::Error>
-{
-    fn new(inner: T, file_name: String) -> Result<FileTask<T>, ::std::io::Error> {
-        let task = FileTask {
-            inner: inner,
-            file_name: file_name,
-        };
-        try!(task.save());
-        Ok(task)
-    }
+/// A name typed by the user without surrounding whitespace, and with
+/// any line breaks, which would split the name across lines of its
+/// task file, made into spaces.
+fn clean_name(name: &str) -> String {
+    name.trim().replace(['\r', '\n'], " ")
+}
 
-    fn save(&self) -> Result<(), ::std::io::Error> {
-        let mut file = File::create(&self.file_name)?;
-        let projection = self.projection();
-        write!(file, "{}\n{:?}", projection.name, projection.status)
+/// Like `prompt`, but the input starts out as `initial` for the user
+/// to edit with backspace. Only printable ASCII can be typed.
+fn prompt_with(message: &str, initial: &str) -> String {
+    ::ncurses::printw(&format!("\n{}\n", message));
+    ::ncurses::curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+    ::ncurses::noecho();
+
+    // Redraw the whole input after every key, from where it started.
+    let y = ::ncurses::getcury(::ncurses::stdscr());
+    let x = ::ncurses::getcurx(::ncurses::stdscr());
+    let mut input = String::from(initial);
+    loop {
+        ::ncurses::mv(y, x);
+        ::ncurses::clrtoeol();
+        ::ncurses::printw(&input);
+        ::ncurses::refresh();
+        match ::ncurses::getch() {
+            10 | 13 | ::ncurses::KEY_ENTER => break,
+            8 | 127 | ::ncurses::KEY_BACKSPACE => {
+                input.pop();
+            }
+            key if (32..127).contains(&key) => input.push(char::from(key as u8)),
+            _ => (),
+        }
     }
 
-    fn save_map_err<E>(&self) -> Result<(), E>
-        where E: From<::std::io::Error>
-    {
-        self.save()
-            .map_err(E::from)
-    }
+    ::ncurses::echo();
+    ::ncurses::curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+    input
 }
 
-impl<T> Task for FileTask<T>
-    where T: Task,
-          Error: From<T::Error>
-{
-    type Error = Error;
-
-    fn goto_next_status(&mut self) -> Result<(), Error> {
-        try!(self.inner.goto_next_status());
-        self.save_map_err()
+/// Draws every key binding, then waits for any key.
+fn show_help(keymap: &Keymap) {
+    ::ncurses::clear();
+    ::ncurses::printw("Key bindings:\n\n");
+    let bindings = keymap.bindings();
+    for (keys, command) in bindings.iter() {
+        ::ncurses::printw(&format!("{:>4}  {}\n", keys, command.description()));
+    }
+    // Goto has no binding of its own: it is a count typed before Bottom.
+    for (keys, _) in bindings.iter().filter(|&(_, command)| *command == Command::Bottom) {
+        let keys = format!("n{}", keys);
+        ::ncurses::printw(&format!("{:>4}  {}\n", keys, Command::Goto(0).description()));
     }
+    ::ncurses::printw("\nPress any key to return.\n");
+    ::ncurses::refresh();
+    ::ncurses::getch();
+}
 
-    fn goto_next_back_status(&mut self) -> Result<(), Error> {
-        try!(self.inner.goto_next_back_status());
-        self.save_map_err()
+/// Draws every field of a task, then waits for any key.
+/// Long names and notes are wrapped to the width of the screen.
+fn show_details(task: &BasicTask) {
+    // Filling the last column would move the cursor to the next line
+    // before the newline is printed, leaving a blank line.
+    let width = cmp::max(2, ::ncurses::getmaxx(::ncurses::stdscr())) as usize - 1;
+    ::ncurses::clear();
+    for line in wrap(&task.name, width) {
+        ::ncurses::printw(&format!("{}\n", line));
+    }
+    ::ncurses::printw("\n");
+    if task.notes.is_empty() {
+        ::ncurses::printw("No notes.\n");
+    } else {
+        for line in wrap(&task.notes, width) {
+            ::ncurses::printw(&format!("{}\n", line));
+        }
     }
 
-    fn projection(&self) -> BasicTask {
-        BasicTask { ..self.inner.projection() }
+    ::ncurses::printw(&format!("\nStatus: {}\n", task.status.name()));
+    if let Some(due) = task.due {
+        ::ncurses::printw(&format!("Due: {}\n", due));
+    }
+    if task.priority != 0 {
+        ::ncurses::printw(&format!("Priority: {}\n", task.priority));
+    }
+    if !task.tags.is_empty() {
+        ::ncurses::printw(&format!("Tags: {}\n", task.tags.join(" ")));
+    }
+    ::ncurses::printw(&format!("Created: {}\n", local_time(&task.created)));
+    if let Some(ref completed) = task.completed {
+        ::ncurses::printw(&format!("Completed: {}\n", local_time(completed)));
+    }
+    if !task.depends_on.is_empty() {
+        let ids = task.depends_on
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        ::ncurses::printw(&format!("Depends on: {}\n", ids.join(", ")));
+    }
+    if let Some(parent) = task.parent {
+        ::ncurses::printw(&format!("Subtask of: {}\n", parent));
+    }
+    if let Some(ref recurrence) = task.recurrence {
+        ::ncurses::printw(&format!("Repeats: {}\n", recurrence.name()));
     }
+    ::ncurses::printw("\nPress any key to return.\n");
+    ::ncurses::refresh();
+    ::ncurses::getch();
 }
 
-impl FileTodoList {
-    pub fn new() -> ado::Result<FileTodoList> {
-        ::std::fs::DirBuilder::new()
-            .recursive(true)
-            .create(PATH)
-            .unwrap();
-
-        let mut todo_list = FileTodoList {
-            ids: ids()?,
-            cache: HashMap::new(),
-        };
-        try!(todo_list.load_all());
-        Ok(todo_list)
-    }
+/// A time as shown to the user, to the minute in the local time zone.
+fn local_time(time: &DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+}
 
-    fn load_all(&mut self) -> Result<(), ::std::io::Error> {
-        for &id in self.ids.iter() {
-            let task = Self::load(id)?;
-            match self.cache.insert(id, task) {
-                // TODO handle this gracefully
-                Some(_) => panic!("Loaded the same task twice"),
-                _ => {}
-            };
+/// Splits text into lines no wider than `width` characters, breaking
+/// between words where it can. Line breaks in the text are kept.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.chars().collect::<Vec<_>>();
+            let used = line.chars().count();
+            if used != 0 && used + 1 + word.len() > width {
+                lines.push(line);
+                line = String::new();
+            } else if used != 0 {
+                line.push(' ');
+            }
+            // Words too long for a line of their own are split.
+            while word.len() > width {
+                let rest = word.split_off(width);
+                lines.push(word.into_iter().collect());
+                word = rest;
+            }
+            line.extend(word);
         }
-        Ok(())
+        lines.push(line);
     }
+    lines
+}
 
-    fn file_name(id: usize) -> String {
-        format!("{}/{:05}", PATH, id)
+/// Cuts text short to at most `width` characters, ending it with
+/// `...` if anything was cut.
+fn truncate_to_width(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return String::from(text);
     }
+    let ellipsis = cmp::min(3, width);
+    let mut truncated = text.chars().take(width - ellipsis).collect::<String>();
+    truncated.push_str(&"..."[..ellipsis]);
+    truncated
+}
 
-    fn load(id: usize) -> Result<FileTask, ::std::io::Error> {
-        let mut file = File::open(&Self::file_name(id))?;
-        let content = {
-            let mut content = String::new();
-            try!(file.read_to_string(&mut content));
-            content
-        };
+/// The order the picker shows tasks in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    /// The list's own order, which `J` and `K` change.
+    List,
+    Status,
+    /// Ignoring case.
+    Name,
+}
 
-        let lines = content.lines().collect::<Vec<_>>();
-        assert_eq!(2, lines.len());
+impl Sort {
+    /// The order after this one, wrapping around.
+    fn next(self) -> Sort {
+        match self {
+            Sort::List => Sort::Status,
+            Sort::Status => Sort::Name,
+            Sort::Name => Sort::List,
+        }
+    }
 
-        let inner = BasicTask {
-            name: String::from(lines[0]),
-            status: Status::from(lines[1]),
-        };
-        Ok(FileTask {
-            file_name: Self::file_name(id),
-            inner: inner,
-        })
+    /// The name of the order, as saved between sessions.
+    fn name(self) -> &'static str {
+        match self {
+            Sort::List => "list",
+            Sort::Status => "status",
+            Sort::Name => "name",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Sort> {
+        [Sort::List, Sort::Status, Sort::Name]
+            .iter()
+            .cloned()
+            .find(|sort| sort.name() == name)
     }
 }
 
-impl TodoList for FileTodoList {
-    type Error = Error;
-    type Task = FileTask;
+struct TaskPicker<T> {
+    /// The position of the cursor in `view`.
+    position: usize,
+    tasks: T,
+    /// The ids of the tasks which are shown, in the order they are shown.
+    /// Movement and selection go by positions in here rather than in
+    /// the whole list.
+    view: Vec<usize>,
+    /// Tasks with these statuses are left out of the view.
+    hidden: Vec<Status>,
+    /// The order of the view.
+    sort: Sort,
+    /// The most recently removed tasks, oldest first.
+    removed: Vec<BasicTask>,
+    /// The most recent search, repeated by `next_match` and `previous_match`.
+    query: String,
+    /// The position of the first task shown on screen.
+    top_visible: usize,
+    /// The most recently yanked task, copied by `paste`.
+    clipboard: Option<BasicTask>,
+    /// The positions selected in visual mode. The cursor is always at
+    /// one end of the range.
+    selection: Option<Range<usize>>,
+}
 
-    fn create(&mut self, name: &str) -> ado::Result<usize> {
-        let id = self.ids.last().unwrap_or(&0) + 1;
+impl<T> TaskPicker<T>
+    where T: TodoList,
+          FrontError: From<T::Error>,
+          FrontError: From<<T::Task as Task>::Error>,
+          ado::Error: From<T::Error>,
+          ado::Error: From<<T::Task as Task>::Error>,
+          T::Error: From<ado::Error>
+{
+    /// A picker showing every task, with the cursor on the first.
+    fn new(tasks: T) -> FrontResult<TaskPicker<T>> {
+        let mut task_picker = TaskPicker {
+            position: 0,
+            tasks,
+            view: Vec::new(),
+            hidden: Vec::new(),
+            sort: Sort::List,
+            removed: Vec::new(),
+            query: String::new(),
+            top_visible: 0,
+            clipboard: None,
+            selection: None,
+        };
+        task_picker.refresh_view()?;
+        Ok(task_picker)
+    }
+
+    /// Works out which tasks are shown again, after the list or what is
+    /// hidden has changed. The cursor stays on the same task if it is
+    /// still shown. If it has been hidden, the cursor moves to the next
+    /// task which is shown, and if it has been removed, the cursor stays
+    /// at the same position.
+    ///
+    /// In the list's own order with nothing hidden, the view comes from
+    /// the ids alone, so no task is read until it is drawn. When sorted,
+    /// subtasks are shown straight after their parent, in `tree_order`.
+    fn refresh_view(&mut self) -> FrontResult<()> {
+        let current = self.id_at(self.position).ok();
+        let tasks = match self.sort {
+            Sort::List if self.hidden.is_empty() => {
+                self.view = self.tasks.ids().collect::<Result<Vec<_>, _>>()?;
+                self.keep_cursor(current, None);
+                return Ok(());
+            }
+            Sort::List => self.tasks.enumerate(),
+            Sort::Status => self.tasks.sorted_by_status(),
+            Sort::Name => self.tasks.sorted_by_name(),
+        };
+        let mut parents = Vec::new();
+        let mut hidden = HashMap::new();
+        for result in tasks {
+            let (id, task) = result?;
+            let view = task.view();
+            parents.push((id, view.parent));
+            hidden.insert(id, self.hidden.contains(&view.status));
+        }
+        let ordered = match self.sort {
+            Sort::List => parents.iter().map(|&(id, _)| (id, 0)).collect(),
+            _ => tree_order(&parents),
+        };
+
+        let mut view = Vec::new();
+        // The number of shown tasks up to and including the current one.
+        let mut shown_to_current = None;
+        for (id, _) in ordered {
+            if !hidden[&id] {
+                view.push(id);
+            }
+            if Some(id) == current {
+                shown_to_current = Some(view.len());
+            }
+        }
+        self.view = view;
+        self.keep_cursor(current, shown_to_current);
+        Ok(())
+    }
 
-        let inner = BasicTask {
-            status: Status::Open,
-            name: String::from(name),
+    /// Puts the cursor back on the task it was on after the view has
+    /// changed, or if that isn't shown, on the task after the number of
+    /// shown tasks which came up to it, or else at the same position.
+    fn keep_cursor(&mut self, current: Option<usize>, shown_to_current: Option<usize>) {
+        let last = cmp::max(1, self.view.len()) - 1;
+        self.position = match (current.and_then(|id| self.view_position(id)), shown_to_current) {
+            (Some(position), _) => position,
+            (None, Some(shown)) => cmp::min(shown, last),
+            (None, None) => cmp::min(self.position, last),
         };
+    }
 
-        let new_task = FileTask::new(inner, Self::file_name(id))?;
+    /// Shows the tasks in the next order, keeping the cursor on the
+    /// same task.
+    fn cycle_sort(&mut self) -> FrontResult<()> {
+        self.sort = self.sort.next();
+        self.refresh_view()
+    }
 
-        if let Some(_) = self.cache.insert(id, new_task) {
-            // TODO Handle gracefully
-            panic!("Created preexisting task");
+    /// Hides Done tasks, or shows them again if they are hidden.
+    fn toggle_done(&mut self) -> FrontResult<()> {
+        match self.hidden.iter().position(|status| *status == Status::Done) {
+            Some(index) => {
+                self.hidden.remove(index);
+            }
+            None => self.hidden.push(Status::Done),
         }
-        self.ids.push(id);
+        self.refresh_view()
+    }
 
-        Ok(id)
+    /// The position of a task in the view, if it is shown.
+    fn view_position(&self, id: usize) -> Option<usize> {
+        self.view.iter().position(|&other| other == id)
     }
 
-    fn enumerate(&self) -> ResultIter<(usize, &Self::Task)> {
-        Box::new(self.ids
-            .iter()
-            .map(move |&id| Ok((id, &self.cache[&id]))))
+    /// Moves the cursor to a task, failing if it isn't shown.
+    fn goto_id(&mut self, id: usize) -> FrontResult<()> {
+        self.position = self.view_position(id).ok_or(Error::NotFound(id))?;
+        Ok(())
     }
 
-    fn remove(&mut self, id: usize) -> ado::Result<Self::Task> {
-        // Fail fast if our file access is broken.
-        fs::remove_file(&format!("{}/{:05}", PATH, id))?;
+    /// Moves the cursor to a task, if it is shown.
+    fn select_id(&mut self, id: usize) {
+        if let Some(position) = self.view_position(id) {
+            self.position = position;
+        }
+    }
 
-        // Load the task and remove it from the cache.
-        let index = self.ids.binary_search(&id)
-            .map_err(|_| Error::NoSuchTask)?;
-        self.ids.remove(index);
+    fn top(&mut self) -> FrontResult<()> {
+        self.position = 0;
+        Ok(())
+    }
 
-        // If the task isn't present, the index search should have failed.
-        let task = self.cache.remove(&id).unwrap();
-        Ok(task)
+    /// Selects the task at a 1-based position, or the last task if the
+    /// list is shorter than that.
+    fn goto_position(&mut self, position: usize) -> FrontResult<()> {
+        self.bottom()?;
+        self.position = cmp::min(self.position, position.saturating_sub(1));
+        Ok(())
     }
 
-    fn find(&self, id: usize) -> ado::Result<&Self::Task> {
-        Ok(&self.cache[&id])
+    fn bottom(&mut self) -> FrontResult<()> {
+        let len = self.len()?;
+        if len == 0 {
+            Err(Error::NoSuchTask)?
+        } else {
+            self.position = len - 1;
+            Ok(())
+        }
     }
 
-    fn find_mut(&mut self, id: usize) -> ado::Result<&mut Self::Task> {
-        match self.cache.get_mut(&id) {
-            None => Err(Error::NoSuchTask),
-            Some(task) => Ok(task),
+    fn down(&mut self) -> FrontResult<()> {
+        let len = self.len()?;
+        if len != 0 && len - 1 != self.position {
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    /// Moves the cursor down by `rows`, stopping at the last task.
+    fn page_down(&mut self, rows: usize) -> FrontResult<()> {
+        let len = self.len()?;
+        if len != 0 {
+            self.position = cmp::min(self.position + rows, len - 1);
+        }
+        Ok(())
+    }
+
+    /// Selects the task drawn on the given line of the list, counting
+    /// from the first visible task, if there is one. At most `rows`
+    /// tasks are visible.
+    fn select_line(&mut self, line: usize, rows: usize) -> FrontResult<bool> {
+        let position = self.top_visible + line;
+        if line >= rows || position >= self.len()? {
+            return Ok(false);
+        }
+        self.position = position;
+        Ok(true)
+    }
+
+    /// Moves the cursor up by `rows`, stopping at the first task.
+    fn page_up(&mut self, rows: usize) -> FrontResult<()> {
+        self.position = self.position.saturating_sub(rows);
+        Ok(())
+    }
+
+    /// How many tasks are shown.
+    fn len(&self) -> FrontResult<usize> {
+        Ok(self.view.len())
+    }
+
+    fn up(&mut self) -> FrontResult<()> {
+        if self.position != 0 {
+            self.position -= 1;
+        }
+        Ok(())
+    }
+
+    /// Finishing a task goes through the list, so that it is refused
+    /// while the task is blocked and repeats recurring tasks.
+    fn right(&mut self) -> FrontResult<()> {
+        let id = self.current_id()?;
+        if self.current()?.projection().status == Status::Open {
+            self.tasks.goto_status(id, Status::Done)?;
+        } else {
+            self.tasks.find_mut(id)?.goto_next_status()?;
+        }
+        self.refresh_view()
+    }
+
+    fn left(&mut self) -> FrontResult<()> {
+        let id = self.current_id()?;
+        self.tasks.find_mut(id)?.goto_next_back_status()?;
+        self.refresh_view()
+    }
+
+    /// Marks the current task as Done, whatever its status, unless it
+    /// is blocked.
+    fn complete(&mut self) -> FrontResult<()> {
+        let id = self.current_id()?;
+        self.tasks.goto_status(id, Status::Done)?;
+        self.refresh_view()
+    }
+
+    /// Moves the current task back to Open, whatever its status.
+    fn reopen(&mut self) -> FrontResult<()> {
+        let id = self.current_id()?;
+        self.tasks.goto_status(id, Status::Open)?;
+        self.refresh_view()
+    }
+
+    /// Renames the current task, unless the new name is empty.
+    fn rename(&mut self, name: &str) -> FrontResult<()> {
+        let name = clean_name(name);
+        if name.is_empty() {
+            return Ok(());
+        }
+        let id = self.current_id()?;
+        self.tasks.find_mut(id)?.rename(&name)?;
+        // Sorting by name may move the task, so follow it.
+        self.refresh_view()?;
+        self.select_id(id);
+        Ok(())
+    }
+
+    /// The id of the task shown at a position, or `NoSuchTask` if the
+    /// position is past the last task shown.
+    fn id_at(&self, position: usize) -> FrontResult<usize> {
+        self.view
+            .get(position)
+            .cloned()
+            .ok_or(FrontError::from(Error::NoSuchTask))
+    }
+
+    fn current_id(&self) -> FrontResult<usize> {
+        self.id_at(self.position)
+    }
+
+    /// The selected task, or `NoSuchTask` if the list is empty.
+    fn current(&self) -> FrontResult<&T::Task> {
+        let id = self.current_id()?;
+        Ok(self.tasks.find(id)?)
+    }
+
+    /// Creates a task and selects it. The name is cleaned up by
+    /// `clean_name`, and the list refuses it if that leaves it empty.
+    fn create(&mut self, name: String) -> FrontResult<usize> {
+        let name = clean_name(&name);
+        let new_id = self.tasks.create(&name)?;
+        self.refresh_view()?;
+        self.goto_id(new_id)?;
+        Ok(new_id)
+    }
+
+    fn remove(&mut self) -> FrontResult<()> {
+        let id = self.current_id()?;
+
+        let task = self.tasks.remove(id)?;
+        self.removed.push(task.projection());
+        if self.removed.len() > UNDO_LIMIT {
+            self.removed.remove(0);
         }
+
+        // The cursor stays at the same position, or the last task.
+        self.refresh_view()
+    }
+
+    /// Selects the first task whose name contains the query, ignoring
+    /// case, and remembers the query for `next_match`.
+    /// The cursor stays put if nothing matches.
+    fn search(&mut self, query: &str) -> FrontResult<()> {
+        self.query = String::from(query);
+        let positions = self.match_positions()?;
+        let first = positions.first().ok_or_else(|| FrontError::NoMatch(self.query.clone()))?;
+        self.position = *first;
+        Ok(())
+    }
+
+    /// Selects the next task matching the last search, wrapping around.
+    fn next_match(&mut self) -> FrontResult<()> {
+        let positions = self.match_positions()?;
+        let next = positions.iter()
+            .find(|&&position| position > self.position)
+            .or_else(|| positions.first())
+            .ok_or_else(|| FrontError::NoMatch(self.query.clone()))?;
+        self.position = *next;
+        Ok(())
+    }
+
+    /// Selects the previous task matching the last search, wrapping around.
+    fn previous_match(&mut self) -> FrontResult<()> {
+        let positions = self.match_positions()?;
+        let previous = positions.iter()
+            .rev()
+            .find(|&&position| position < self.position)
+            .or_else(|| positions.last())
+            .ok_or_else(|| FrontError::NoMatch(self.query.clone()))?;
+        self.position = *previous;
+        Ok(())
     }
 
-    fn iter(&self) -> ResultIter<&Self::Task> {
-        let iter = self.cache
+    /// The positions in the view of the tasks which match the last
+    /// search. Hidden tasks which match aren't included.
+    fn match_positions(&self) -> FrontResult<Vec<usize>> {
+        if self.query.is_empty() {
+            return Err(FrontError::NoSearch);
+        }
+        let matches = self.tasks
+            .search(&self.query)
+            .map(|result| result.map(|(id, _)| id))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.view
             .iter()
-            .map(|(_, task)| Ok(task));
-        Box::new(iter)
+            .enumerate()
+            .filter(|&(_, id)| matches.contains(id))
+            .map(|(position, _)| position)
+            .collect())
     }
 
-    fn iter_mut(&mut self) -> ResultIter<&mut Self::Task> {
-        let iter = self.cache
-            .iter_mut()
-            .map(|(_, task)| Ok(task));
-        Box::new(iter)
+    /// Re-creates the most recently removed task and selects it.
+    ///
+    /// The task gets a new id and timestamps, but keeps everything else.
+    fn undo(&mut self) -> FrontResult<()> {
+        let removed = self.removed.pop().ok_or(FrontError::NothingToUndo)?;
+        // Not `create`, so that the name comes back as it was, without
+        // going through `clean_name`.
+        let id = match self.tasks.create(&removed.name) {
+            Ok(id) => id,
+            Err(err) => {
+                self.removed.push(removed);
+                return Err(FrontError::from(err));
+            }
+        };
+
+        let task = self.tasks.find_mut(id)?;
+        task.goto_status(removed.status.clone())?;
+        copy_fields(task, &removed)?;
+        self.refresh_view()?;
+        self.select_id(id);
+        Ok(())
     }
 
-    fn into_iter<'a>(self) -> ResultIter<'a, Self::Task> {
-        let iter = self.cache
-            .into_iter()
-            .map(|(_, task)| Ok(task));
-        Box::new(iter)
+    /// Copies the current task into the clipboard, for `paste`.
+    fn yank(&mut self) -> FrontResult<()> {
+        self.clipboard = Some(self.current()?.projection());
+        Ok(())
+    }
+
+    /// Starts a selection holding just the current task.
+    fn select(&mut self) -> FrontResult<()> {
+        self.current_id()?;
+        self.selection = Some(self.position..self.position + 1);
+        Ok(())
+    }
+
+    /// Stretches the selection from its other end to the cursor, after
+    /// the cursor has moved from `previous`.
+    fn extend_selection(&mut self, previous: usize) {
+        if let Some(ref mut selection) = self.selection {
+            let anchor = if previous == selection.start {
+                selection.end - 1
+            } else {
+                selection.start
+            };
+            *selection = cmp::min(anchor, self.position)..cmp::max(anchor, self.position) + 1;
+        }
+    }
+
+    /// The ids of the selected tasks, in the order they are shown.
+    fn selected_ids(&self) -> FrontResult<Vec<usize>> {
+        let selection = self.selection.clone().unwrap_or(self.position..self.position);
+        Ok(self.view[selection].to_vec())
+    }
+
+    /// Removes every selected task, each of which can be brought back
+    /// with undo, and ends the selection.
+    ///
+    /// Every task is read before any is removed, so a task which can't be
+    /// read leaves the list as it was.
+    fn remove_selected(&mut self) -> FrontResult<()> {
+        let ids = self.selected_ids()?;
+        let start = self.selection.take().map_or(self.position, |selection| selection.start);
+        for &id in ids.iter() {
+            self.tasks.find(id)?;
+        }
+
+        for id in ids {
+            let task = self.tasks.remove(id)?;
+            self.removed.push(task.projection());
+            if self.removed.len() > UNDO_LIMIT {
+                self.removed.remove(0);
+            }
+        }
+        self.position = start;
+        self.refresh_view()
+    }
+
+    /// Marks every selected task as Done and ends the selection.
+    ///
+    /// Nothing is changed if any selected task depends on one which is
+    /// neither Done nor selected. Otherwise the tasks are finished
+    /// dependencies first, so that none of them is refused as blocked.
+    fn complete_selected(&mut self) -> FrontResult<()> {
+        let ids = self.selected_ids()?;
+        self.selection = None;
+        for &id in ids.iter() {
+            let depends_on = self.tasks.find(id)?.projection().depends_on;
+            for result in self.tasks.ids() {
+                let other = result.map_err(Error::from)?;
+                if depends_on.contains(&other) && !ids.contains(&other) &&
+                   self.tasks.find(other)?.projection().status != Status::Done {
+                    return Err(FrontError::from(Error::Blocked(id)));
+                }
+            }
+        }
+
+        let mut remaining = ids;
+        while let Some(&blocked) = remaining.first() {
+            let before = remaining.len();
+            let mut still_blocked = Vec::new();
+            for id in remaining {
+                if self.tasks.is_blocked(id)? {
+                    still_blocked.push(id);
+                } else {
+                    self.tasks.goto_status(id, Status::Done)?;
+                }
+            }
+            if still_blocked.len() == before {
+                self.refresh_view()?;
+                return Err(FrontError::from(Error::Blocked(blocked)));
+            }
+            remaining = still_blocked;
+        }
+        self.refresh_view()
     }
 }
 
-fn ids() -> Result<Vec<usize>, ::std::io::Error> {
-    let read_dir = ::std::fs::read_dir(PATH)?;
+/// Copies everything but the name and status of `from` onto `task`.
+fn copy_fields<U>(task: &mut U, from: &BasicTask) -> Result<(), U::Error>
+    where U: Task
+{
+    task.set_due(from.due)?;
+    task.set_priority(from.priority)?;
+    for tag in from.tags.iter() {
+        task.add_tag(tag)?;
+    }
+    task.set_notes(&from.notes)?;
+    task.set_depends_on(&from.depends_on)?;
+    task.set_parent(from.parent)?;
+    task.set_recurrence(from.recurrence.clone())?;
+    Ok(())
+}
 
-    // TODO: Report errors in some way instead of swallowing them in flat_map.
-    // Get a usize for each file name in the data path, where possible.
-    let mut ids = read_dir.flat_map(Result::ok)
-        .map(|entry| entry.file_name())
-        .flat_map(OsString::into_string)
-        .flat_map(|name| name.parse())
-        .collect::<Vec<_>>();
+impl<T> TaskPicker<T>
+    where T: Reorder + Reload,
+          FrontError: From<T::Error>,
+          FrontError: From<<T::Task as Task>::Error>,
+          ado::Error: From<T::Error>,
+          ado::Error: From<<T::Task as Task>::Error>,
+          T::Error: From<ado::Error>
+{
+    /// Reloads changed tasks, keeping the cursor on the list, and
+    /// returns a message for each file which was skipped.
+    fn reload(&mut self) -> FrontResult<Vec<String>> {
+        let messages = self.tasks.reload_if_changed()?;
+        self.refresh_view()?;
+        Ok(messages)
+    }
 
-    ids.sort();
-    Ok(ids)
+    /// The index of a task in the whole list, including hidden tasks.
+    fn list_index(&self, id: usize) -> FrontResult<usize> {
+        for (index, result) in self.tasks.ids().enumerate() {
+            if result? == id {
+                return Ok(index);
+            }
+        }
+        Err(FrontError::from(Error::NotFound(id)))
+    }
+
+    /// Moves the current task above the task shown above it, past any
+    /// hidden tasks in between. Does nothing at the top of the list.
+    fn move_up(&mut self) -> FrontResult<()> {
+        if self.sort != Sort::List {
+            return Err(FrontError::Sorted(self.sort));
+        }
+        if self.position == 0 {
+            return Ok(());
+        }
+        let id = self.current_id()?;
+        let index = self.list_index(self.id_at(self.position - 1)?)?;
+        self.tasks.move_to(id, index)?;
+        self.refresh_view()
+    }
+
+    /// Moves the current task below the task shown below it, past any
+    /// hidden tasks in between. Does nothing at the bottom of the list.
+    fn move_down(&mut self) -> FrontResult<()> {
+        if self.sort != Sort::List {
+            return Err(FrontError::Sorted(self.sort));
+        }
+        if self.position + 1 >= self.len()? {
+            return Ok(());
+        }
+        let id = self.current_id()?;
+        let index = self.list_index(self.id_at(self.position + 1)?)?;
+        self.tasks.move_to(id, index)?;
+        self.refresh_view()
+    }
+
+    /// Creates an open copy of the clipboard task below the current one
+    /// and selects it.
+    fn paste(&mut self) -> FrontResult<()> {
+        let copied = self.clipboard.clone().ok_or(FrontError::EmptyClipboard)?;
+        let below = match self.current_id() {
+            Ok(current) => Some(self.list_index(current)? + 1),
+            Err(_) => None,
+        };
+        let id = self.tasks.create(&copied.name)?;
+        copy_fields(self.tasks.find_mut(id)?, &copied)?;
+        if let Some(index) = below {
+            self.tasks.move_to(id, index)?;
+        }
+        self.refresh_view()?;
+        self.select_id(id);
+        Ok(())
+    }
+}
+
+impl<T> Display for TaskPicker<T>
+    where T: TodoList
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", HEADER)?;
+        for (position, &id) in self.view.iter().enumerate() {
+            let task = self.tasks.find(id).map_err(|_| fmt::Error)?;
+            let depth = ancestors(&self.tasks, id).map_err(|_| fmt::Error)?.len();
+            let marker = if position == self.position { ">" } else { " " };
+            // Text has no width to fit, so nothing is cut short.
+            write!(f, "\n{}", row(marker, depth, &task.view(), usize::MAX))?;
+        }
+        Ok(())
+    }
+}
+
+/// Shows the cursor position and how many tasks there are, rather than
+/// every task.
+impl<T> fmt::Debug for TaskPicker<T>
+    where T: TodoList
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("TaskPicker");
+        debug.field("position", &self.position);
+        debug.field("len", &self.view.len());
+        debug.finish_non_exhaustive()
+    }
+}
+
+/// The column headings shown above the list.
+const HEADER: &str = "  WONT TODO DONE";
+
+/// The screen line of the first visible task, below the header.
+const LIST_TOP: i32 = 1;
+
+/// The status padded to sit under its heading in `HEADER`.
+fn status_column(status: &Status) -> &'static str {
+    match *status {
+        Status::Open => "     [ ]      ",
+        Status::Done => "           [x]",
+        Status::Wont => "----          ",
+    }
+}
+
+/// Formats one line of the list, starting with the cursor marker.
+/// Subtasks are indented by `depth` levels below the top of the tree.
+/// The name and what follows it are cut short to fit in `width`.
+fn row(marker: &str, depth: usize, view: &TaskView, width: usize) -> String {
+    let prefix = format!("{} {} {}{}",
+                         marker,
+                         status_column(&view.status),
+                         "  ".repeat(depth),
+                         priority_marker(view.priority));
+    let mut rest = view.name.to_string();
+    if let Some(due) = view.due {
+        rest.push_str(&format!(" (due {})", due));
+    }
+    if !view.tags.is_empty() {
+        rest.push_str(&format!(" {}", view.tags.join(" ")));
+    }
+    let room = width.saturating_sub(prefix.chars().count());
+    prefix + &truncate_to_width(&rest, room)
+}
+
+/// Colour pairs for each status; Open tasks use the default colours.
+const DONE_PAIR: i16 = 1;
+const WONT_PAIR: i16 = 2;
+const OVERDUE_PAIR: i16 = 3;
+
+/// Sets up the status colour pairs, if the terminal supports colour.
+fn init_colours() {
+    if ::ncurses::has_colors() {
+        ::ncurses::start_color();
+        ::ncurses::use_default_colors();
+        ::ncurses::init_pair(DONE_PAIR, ::ncurses::COLOR_GREEN, -1);
+        ::ncurses::init_pair(WONT_PAIR, ::ncurses::COLOR_RED, -1);
+        ::ncurses::init_pair(OVERDUE_PAIR, ::ncurses::COLOR_YELLOW, -1);
+    }
+}
+
+/// The number of task rows which fit on the screen, leaving room for
+/// the header, a message line and the status line.
+fn page_height() -> usize {
+    let (mut height, mut width) = (0, 0);
+    ::ncurses::getmaxyx(::ncurses::stdscr(), &mut height, &mut width);
+    cmp::max(1, height - 3) as usize
+}
+
+/// The first position to show so that `position` is on screen,
+/// scrolling from `top_visible` only as far as needed.
+fn scroll_offset(top_visible: usize, position: usize, rows: usize) -> usize {
+    if position < top_visible {
+        position
+    } else if position >= top_visible + rows {
+        position + 1 - rows
+    } else {
+        top_visible
+    }
+}
+
+/// Draws the visible part of the task picker line by line, like its
+/// `Display` output, but with each task coloured by its status and
+/// overdue tasks highlighted.
+///
+/// The view is scrolled first to keep the cursor on screen.
+fn render<T>(task_picker: &mut TaskPicker<T>)
+    where T: TodoList
+{
+    let colours = ::ncurses::has_colors();
+    let today = Local::now().date_naive();
+
+    let rows = page_height();
+    let width = cmp::max(0, ::ncurses::getmaxx(::ncurses::stdscr())) as usize;
+    task_picker.top_visible = scroll_offset(task_picker.top_visible, task_picker.position, rows);
+    let first = task_picker.top_visible;
+
+    ::ncurses::printw(&format!("{}\n", HEADER));
+    // Each row is drawn on its own line, so rows which haven't changed
+    // since the last refresh aren't sent to the terminal again. Only the
+    // tasks on screen and their parents are read, so a malformed task
+    // file is reported in its row when it is first shown.
+    let mut line = ::ncurses::getcury(::ncurses::stdscr());
+    for (position, &id) in task_picker.view.iter().enumerate().skip(first).take(rows) {
+        let marker = if position == task_picker.position { ">" } else { " " };
+        let view = match task_picker.tasks.find(id) {
+            Ok(task) => task.view(),
+            Err(err) => {
+                ::ncurses::mvprintw(line, 0, &truncate_to_width(&format!("{} {}", marker, err), width));
+                line += 1;
+                continue;
+            }
+        };
+        let depth = ancestors(&task_picker.tasks, id).map_or(0, |ancestors| ancestors.len());
+        let selected = task_picker.selection
+            .as_ref()
+            .is_some_and(|selection| selection.contains(&position));
+        let attributes = if is_overdue(&view, today) {
+            // Bold still stands out without colour.
+            if colours {
+                Some(::ncurses::COLOR_PAIR(OVERDUE_PAIR) | ::ncurses::A_BOLD())
+            } else {
+                Some(::ncurses::A_BOLD())
+            }
+        } else if colours {
+            match view.status {
+                Status::Open => None,
+                Status::Done => Some(::ncurses::COLOR_PAIR(DONE_PAIR)),
+                Status::Wont => Some(::ncurses::COLOR_PAIR(WONT_PAIR)),
+            }
+        } else {
+            None
+        };
+        let attributes = if selected {
+            Some(attributes.unwrap_or(0) | ::ncurses::A_REVERSE())
+        } else {
+            attributes
+        };
+
+        match attributes {
+            Some(attributes) => {
+                ::ncurses::attron(attributes);
+                ::ncurses::mvprintw(line, 0, &row(marker, depth, &view, width));
+                ::ncurses::attroff(attributes);
+            }
+            _ => {
+                ::ncurses::mvprintw(line, 0, &row(marker, depth, &view, width));
+            }
+        }
+        line += 1;
+    }
+    ::ncurses::mv(line, 0);
+
+    // Draw the summary on the bottom line, then carry on where we were.
+    let y = ::ncurses::getcury(::ncurses::stdscr());
+    let x = ::ncurses::getcurx(::ncurses::stdscr());
+    ::ncurses::mv(::ncurses::getmaxy(::ncurses::stdscr()) - 1, 0);
+    match summary(task_picker) {
+        Ok(summary) => ::ncurses::printw(&summary),
+        Err(err) => ::ncurses::printw(&err.to_string()),
+    };
+    ::ncurses::mv(y, x);
+}
+
+/// The task's parent, its parent's parent and so on, reading only
+/// those tasks. Parents which have been removed from the list end the
+/// chain, and so does coming back round to a task already in it, which
+/// a hand-edited file could make happen.
+fn ancestors<T>(todo_list: &T, id: usize) -> Result<Vec<usize>, T::Error>
+    where T: TodoList
+{
+    let mut ancestors = Vec::new();
+    let mut parent = todo_list.find(id)?.view().parent;
+    while let Some(next) = parent {
+        if next == id || ancestors.contains(&next) || !todo_list.exists(next) {
+            break;
+        }
+        ancestors.push(next);
+        parent = todo_list.find(next)?.view().parent;
+    }
+    Ok(ancestors)
+}
+
+/// Orders tasks, given by id along with their parent, so that each is
+/// followed by its children and their children in turn, and pairs each
+/// with its depth. Siblings keep the order they were given in.
+///
+/// A task whose parent isn't given is at the top, and so is the first
+/// task reached of those caught in a cycle, which a hand-edited file
+/// could make.
+fn tree_order(parents: &[(usize, Option<usize>)]) -> Vec<(usize, usize)> {
+    let ids = parents.iter().map(|&(id, _)| id).collect::<HashSet<_>>();
+    let mut children = HashMap::<usize, Vec<usize>>::new();
+    let mut roots = Vec::new();
+    for &(id, parent) in parents {
+        match parent {
+            Some(parent) if ids.contains(&parent) => children.entry(parent).or_default().push(id),
+            _ => roots.push(id),
+        }
+    }
+
+    let mut ordered = Vec::with_capacity(parents.len());
+    let mut visited = HashSet::new();
+    // Tasks in a cycle can't be reached from the top, so each task not
+    // yet reached is tried after the roots.
+    for start in roots.into_iter().chain(parents.iter().map(|&(id, _)| id)) {
+        let mut stack = vec![(start, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            ordered.push((id, depth));
+            if let Some(children) = children.get(&id) {
+                stack.extend(children.iter().rev().map(|&child| (child, depth + 1)));
+            }
+        }
+    }
+    ordered
+}
+
+/// Whether a task was due before `today` and still isn't Done.
+fn is_overdue(task: &TaskView, today: NaiveDate) -> bool {
+    match task.due {
+        Some(due) => due < today && task.status != Status::Done,
+        None => false,
+    }
+}
+
+/// The task counts and cursor position for the status line,
+/// e.g. "3 open, 2 done, 1 wont - 6 total [2/6]". The position is among
+/// the tasks which are shown, and any sort and hidden statuses are named.
+///
+/// The counts by status are left out until every task has been read,
+/// so that drawing the status line doesn't read the whole list.
+fn summary<T>(task_picker: &TaskPicker<T>) -> Result<String, T::Error>
+    where T: TodoList
+{
+    if task_picker.tasks.is_empty()? {
+        return Ok(String::from("no tasks"));
+    }
+    let mut summary = String::new();
+    if task_picker.tasks.is_loaded() {
+        let counts = task_picker.tasks.count_by_status()?;
+        let count = |status: Status| counts.get(status.name()).cloned().unwrap_or(0);
+        summary.push_str(&format!("{} open, {} done, {} wont - ",
+                                  count(Status::Open),
+                                  count(Status::Done),
+                                  count(Status::Wont)));
+    }
+    let shown = task_picker.view.len();
+    summary.push_str(&format!("{} total [{}/{}]",
+                              task_picker.tasks.len()?,
+                              cmp::min(task_picker.position + 1, shown),
+                              shown));
+    if task_picker.sort != Sort::List {
+        summary.push_str(&format!(" (sorted by {})", task_picker.sort.name()));
+    }
+    if !task_picker.hidden.is_empty() {
+        let names = task_picker.hidden
+            .iter()
+            .map(Status::name)
+            .collect::<Vec<_>>();
+        summary.push_str(&format!(" ({} hidden)", names.join(", ")));
+    }
+    Ok(summary)
+}
+
+/// One `!` per priority level, capped at three, followed by a space.
+/// Tasks without a priority get no marker.
+fn priority_marker(priority: u8) -> String {
+    match priority {
+        0 => String::new(),
+        p => format!("{} ", "!".repeat(cmp::min(p, 3) as usize)),
+    }
+}
+
+/// A list whose tasks can be rearranged by hand.
+trait Reorder: TodoList {
+    /// Moves a task to the given position in the list, or to the end if
+    /// the position is past the end, and persists the new order.
+    fn move_to(&mut self, id: usize, index: usize) -> Result<(), Self::Error>;
+}
+
+/// A list which can pick up changes made to its files by other programs.
+trait Reload: TodoList {
+    /// Reloads the tasks whose files have changed since they were last
+    /// read or written, including tasks which were added or removed.
+    ///
+    /// Returns a message for each file which was skipped, for the
+    /// caller to show.
+    fn reload_if_changed(&mut self) -> Result<Vec<String>, Self::Error>;
+}
+
+/// A list whose storage can be checked for files it can't read.
+trait Check: TodoList {
+    /// Reads every task file afresh without changing anything.
+    fn check(&self) -> Result<CheckReport<Self::Error>, Self::Error>;
+}
+
+/// What `Check::check` found in a list's storage.
+struct CheckReport<E> {
+    /// Each task file which couldn't be read, along with the reason.
+    unreadable: Vec<(PathBuf, E)>,
+    /// Files which were skipped because they aren't task files.
+    skipped: Vec<PathBuf>,
+}
+
+/// A list which can move finished tasks out of the way, keeping them
+/// to be read back later.
+trait Archive: TodoList {
+    /// Moves every Done task out of the list and into the archive,
+    /// returning how many were moved.
+    fn archive_done(&mut self) -> Result<usize, Self::Error>;
+
+    /// The archived tasks by id, in the order they were archived.
+    fn list_archived(&self) -> Result<Vec<(usize, BasicTask)>, Self::Error>;
+}
+
+/// A list which can be bulk-loaded from plain text.
+trait Import: TodoList
+    where Self::Error: From<::std::io::Error>
+{
+    /// Creates an Open task for each non-empty line of `reader`, named
+    /// by the line without surrounding whitespace, and returns their ids.
+    fn import_lines<R: BufRead>(&mut self, reader: R) -> Result<Vec<usize>, Self::Error> {
+        let mut names = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let name = line.trim();
+            if !name.is_empty() {
+                names.push(String::from(name));
+            }
+        }
+        let names = names.iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        self.create_all(&names)
+    }
+}
+
+/// A Task source backed by flat files, one per task, written in the
+/// format `F`.
+pub struct FileTodoList<F = Lines> {
+    path: PathBuf,
+    /// Every task in the list, each read from its file on first access.
+    cache: HashMap<usize, OnceCell<FileTask<BasicTask, F>>>,
+    /// The ids of the tasks, in the order they are listed.
+    order: Vec<usize>,
+    load_errors: Vec<Error>,
+    /// The files in the directory which aren't task files, as of the
+    /// last time it was listed.
+    skipped: Vec<String>,
+    /// Held until the list is dropped.
+    _lock: Lock,
+}
+
+/// An advisory lock on a list's directory, held by creating a `.lock`
+/// file in it and released by removing the file on drop.
+struct Lock {
+    file_name: PathBuf,
+}
+
+impl Lock {
+    /// Takes the lock on the directory, failing with `Error::Locked`
+    /// if another instance holds it.
+    fn acquire(path: &Path) -> ado::Result<Lock> {
+        let file_name = path.join(".lock");
+        let mut file = match fs::OpenOptions::new().write(true).create_new(true).open(&file_name) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::AlreadyExists => {
+                return Err(Error::Locked)
+            }
+            Err(err) => return Err(Error::from(err)),
+        };
+        let lock = Lock { file_name };
+
+        // The id helps a person work out whether the lock is stale.
+        write!(file, "{}", ::std::process::id())?;
+        Ok(lock)
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.file_name);
+    }
+}
+
+/// A Task source storing each task as a JSON file.
+#[cfg(feature = "json")]
+pub type JsonTodoList = FileTodoList<Json>;
+
+pub struct FileTask<T = BasicTask, F = Lines> {
+    file_name: PathBuf,
+    inner: T,
+    format: PhantomData<F>,
+    /// The modification time of the file when it was last read or written.
+    modified: Option<SystemTime>,
+}
+
+/// How a task is written to, and read back from, its own file.
+pub trait Format: 'static {
+    /// Appended to a task's id to give the name of its file.
+    const EXTENSION: &'static str;
+
+    fn write(task: &BasicTask, file: &mut File) -> Result<(), ::std::io::Error>;
+
+    /// Parses the contents of a task file, describing what is wrong
+    /// with it on failure.
+    ///
+    /// `modified` is the file's modification time, which stands in for
+    /// the creation time of tasks written before it was recorded.
+    fn parse(content: &str, modified: DateTime<Utc>) -> Result<BasicTask, String>;
+}
+
+/// One field per line, in a fixed order.
+pub struct Lines;
+
+/// A serialized `BasicTask`.
+#[cfg(feature = "json")]
+pub struct Json;
+
+impl<T, F> FileTask<T, F>
+    where T: Task,
+          F: Format,
+          Error: From<T::Error>
+{
+    fn new(inner: T, file_name: PathBuf) -> Result<FileTask<T, F>, ::std::io::Error> {
+        let mut task = FileTask {
+            inner,
+            file_name,
+            format: PhantomData,
+            modified: None,
+        };
+        task.save()?;
+        Ok(task)
+    }
+
+    /// Writes the task to its file, replacing the old version atomically
+    /// so a crash mid-write can never leave a truncated task file.
+    fn save(&mut self) -> Result<(), ::std::io::Error> {
+        replace_file(&self.file_name,
+                     |file| F::write(&self.projection(), file))?;
+        self.modified = fs::metadata(&self.file_name)?.modified().ok();
+        Ok(())
+    }
+
+    fn save_map_err<E>(&mut self) -> Result<(), E>
+        where E: From<::std::io::Error>
+    {
+        self.save()
+            .map_err(E::from)
+    }
+}
+
+impl<T, F> Task for FileTask<T, F>
+    where T: Task,
+          F: Format,
+          Error: From<T::Error>
+{
+    type Error = Error;
+
+    fn goto_next_status(&mut self) -> Result<(), Error> {
+        self.inner.goto_next_status()?;
+        self.save_map_err()
+    }
+
+    fn goto_next_back_status(&mut self) -> Result<(), Error> {
+        self.inner.goto_next_back_status()?;
+        self.save_map_err()
+    }
+
+    fn goto_status(&mut self, target: Status) -> Result<(), Error> {
+        self.inner.goto_status(target)?;
+        self.save_map_err()
+    }
+
+    fn rename(&mut self, name: &str) -> Result<(), Error> {
+        self.inner.rename(name)?;
+        self.save_map_err()
+    }
+
+    fn set_priority(&mut self, priority: u8) -> Result<(), Error> {
+        self.inner.set_priority(priority)?;
+        self.save_map_err()
+    }
+
+    fn add_tag(&mut self, tag: &str) -> Result<(), Error> {
+        self.inner.add_tag(tag)?;
+        self.save_map_err()
+    }
+
+    fn set_notes(&mut self, notes: &str) -> Result<(), Error> {
+        self.inner.set_notes(notes)?;
+        self.save_map_err()
+    }
+
+    fn set_due(&mut self, due: Option<NaiveDate>) -> Result<(), Error> {
+        self.inner.set_due(due)?;
+        self.save_map_err()
+    }
+
+    fn set_depends_on(&mut self, ids: &[usize]) -> Result<(), Error> {
+        self.inner.set_depends_on(ids)?;
+        self.save_map_err()
+    }
+
+    fn set_parent(&mut self, parent: Option<usize>) -> Result<(), Error> {
+        self.inner.set_parent(parent)?;
+        self.save_map_err()
+    }
+
+    fn set_recurrence(&mut self, recurrence: Option<Recurrence>) -> Result<(), Error> {
+        self.inner.set_recurrence(recurrence)?;
+        self.save_map_err()
+    }
+
+    fn projection(&self) -> BasicTask {
+        BasicTask { ..self.inner.projection() }
+    }
+
+    fn view(&self) -> TaskView<'_> {
+        self.inner.view()
+    }
+}
+
+impl<F: Format> FileTodoList<F> {
+    /// Loads the list stored in `$ADO_DIR`, or `./.ado/` if it is unset.
+    pub fn new() -> ado::Result<FileTodoList<F>> {
+        Self::with_path(default_path())
+    }
+
+    /// Loads the list stored in the given directory, creating the
+    /// directory if it doesn't exist.
+    ///
+    /// Only the directory listing is read; each task's file is read
+    /// when the task is first used.
+    pub fn with_path<P: Into<PathBuf>>(path: P) -> ado::Result<FileTodoList<F>> {
+        let path = path.into();
+        ::std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(&path)?;
+
+        let lock = Lock::acquire(&path)?;
+        let (order, skipped) = ids(&path, F::EXTENSION)?;
+        let mut cache = HashMap::with_capacity(order.len());
+        for &id in order.iter() {
+            if cache.insert(id, OnceCell::new()).is_some() {
+                return Err(Error::DuplicateTask(id));
+            }
+        }
+        let mut todo_list = FileTodoList {
+            order,
+            _lock: lock,
+            path,
+            cache,
+            load_errors: Vec::new(),
+            skipped,
+        };
+        todo_list.load_order()?;
+        Ok(todo_list)
+    }
+
+    /// The errors for malformed task files which were skipped on load.
+    pub fn load_errors(&self) -> &[Error] {
+        &self.load_errors
+    }
+
+    /// The names of the files in the list's directory which were skipped
+    /// because they aren't task files.
+    pub fn skipped_files(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// Reads every task which hasn't been read yet. Malformed task
+    /// files are dropped from the list and kept in `load_errors`, so that
+    /// one bad file doesn't make the rest of the list unusable.
+    pub fn load_all(&mut self) -> ado::Result<()> {
+        for id in self.order.clone() {
+            match self.loaded(id) {
+                Ok(_) => (),
+                Err(err @ Error::MalformedTask { .. }) => {
+                    self.order.retain(|&other| other != id);
+                    self.cache.remove(&id);
+                    self.load_errors.push(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// The task with the given id, reading its file if this is the
+    /// first time it has been used.
+    fn loaded(&self, id: usize) -> ado::Result<&FileTask<BasicTask, F>> {
+        let cell = self.cache.get(&id).ok_or(Error::NotFound(id))?;
+        match cell.get() {
+            Some(task) => Ok(task),
+            None => {
+                let task = self.load(id)?;
+                Ok(cell.get_or_init(|| task))
+            }
+        }
+    }
+
+    /// As `loaded`, but `None` for a malformed task file, so that
+    /// iterating over the list skips it as `load_all` would.
+    fn loaded_or_skipped(&self, id: usize) -> Option<ado::Result<&FileTask<BasicTask, F>>> {
+        match self.loaded(id) {
+            Err(Error::MalformedTask { .. }) => None,
+            result => Some(result),
+        }
+    }
+
+    fn loaded_mut(&mut self, id: usize) -> ado::Result<&mut FileTask<BasicTask, F>> {
+        self.loaded(id)?;
+        Ok(self.cache.get_mut(&id).and_then(OnceCell::get_mut).unwrap())
+    }
+
+    /// Reads every task which hasn't been read yet, returning the errors
+    /// for the files which couldn't be.
+    fn load_each(&self) -> Vec<Error> {
+        self.order
+            .iter()
+            .filter_map(|&id| self.loaded(id).err())
+            .collect()
+    }
+
+    /// Arranges the loaded tasks in the order saved by `move_to`.
+    ///
+    /// The order file is only written when tasks are moved, so ids it
+    /// doesn't mention (newer tasks) follow in ascending order, and ids
+    /// of tasks which no longer exist are ignored.
+    fn load_order(&mut self) -> ado::Result<()> {
+        let content = match File::open(self.order_file_name()) {
+            Ok(mut file) => {
+                let mut content = String::new();
+                file.read_to_string(&mut content)?;
+                content
+            }
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(Error::from(err)),
+        };
+
+        let mut order = Vec::with_capacity(self.order.len());
+        for line in content.lines().filter(|line| !line.is_empty()) {
+            let id = line.trim().parse::<usize>()?;
+            if self.cache.contains_key(&id) && !order.contains(&id) {
+                order.push(id);
+            }
+        }
+        for &id in self.order.iter() {
+            if !order.contains(&id) {
+                order.push(id);
+            }
+        }
+        self.order = order;
+        Ok(())
+    }
+
+    fn save_order(&self) -> ado::Result<()> {
+        replace_file(&self.order_file_name(), |file| {
+            for id in self.order.iter() {
+                writeln!(file, "{}", id)?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn file_name(&self, id: usize) -> PathBuf {
+        self.path.join(format!("{:05}{}", id, F::EXTENSION))
+    }
+
+    fn next_id_file_name(&self) -> PathBuf {
+        self.path.join("next_id")
+    }
+
+    fn order_file_name(&self) -> PathBuf {
+        self.path.join("order")
+    }
+
+    fn trash_path(&self) -> PathBuf {
+        self.path.join(".trash")
+    }
+
+    fn trash_file_name(&self, id: usize) -> PathBuf {
+        self.trash_path().join(format!("{:05}{}", id, F::EXTENSION))
+    }
+
+    fn archive_path(&self) -> PathBuf {
+        self.path.join("archive")
+    }
+
+    fn archive_file_name(&self, id: usize) -> PathBuf {
+        self.archive_path().join(format!("{:05}{}", id, F::EXTENSION))
+    }
+
+    /// Moves a task's file into the trash, keeping its id.
+    fn trash(&self, id: usize) -> ado::Result<()> {
+        ::std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(self.trash_path())?;
+        fs::rename(self.file_name(id), self.trash_file_name(id))?;
+        Ok(())
+    }
+
+    /// Brings a removed task back from the trash under its old id,
+    /// placing it at the end of the list.
+    pub fn restore(&mut self, id: usize) -> ado::Result<usize> {
+        if self.cache.contains_key(&id) || self.file_name(id).exists() {
+            return Err(Error::DuplicateTask(id));
+        }
+        match fs::rename(self.trash_file_name(id), self.file_name(id)) {
+            Ok(()) => (),
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => {
+                return Err(Error::NotFound(id))
+            }
+            Err(err) => return Err(Error::from(err)),
+        }
+
+        let task = match self.load(id) {
+            Ok(task) => task,
+            Err(err) => {
+                // Don't leave a file we couldn't load in the list.
+                let _ = fs::rename(self.file_name(id), self.trash_file_name(id));
+                return Err(err);
+            }
+        };
+        self.cache.insert(id, OnceCell::from(task));
+        self.order.push(id);
+        Ok(id)
+    }
+
+    /// Permanently deletes every task in the trash.
+    pub fn empty_trash(&mut self) -> ado::Result<()> {
+        match fs::remove_dir_all(self.trash_path()) {
+            Ok(()) => Ok(()),
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(Error::from(err)),
+        }
+    }
+
+    /// Reserves a new id, never handing out the same id twice for the
+    /// lifetime of the list, even after the highest task is removed.
+    ///
+    /// The counter is persisted before the id is returned.
+    fn allocate_id(&mut self) -> ado::Result<usize> {
+        Ok(self.allocate_ids(1)?.start)
+    }
+
+    /// Reserves `count` consecutive ids, in the same way as `allocate_id`.
+    fn allocate_ids(&mut self, count: usize) -> ado::Result<Range<usize>> {
+        let stored = match File::open(self.next_id_file_name()) {
+            Ok(mut file) => {
+                let mut content = String::new();
+                file.read_to_string(&mut content)?;
+                content.trim().parse::<usize>()?
+            }
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(Error::from(err)),
+        };
+
+        // Lists created before the counter existed start after their last id.
+        let mut start = cmp::max(stored, self.order.iter().max().unwrap_or(&0) + 1);
+
+        // Skipped malformed task files, and trashed and archived tasks,
+        // still occupy their ids.
+        let taken = |id| {
+            self.file_name(id).exists() || self.trash_file_name(id).exists() ||
+            self.archive_file_name(id).exists()
+        };
+        while let Some(taken) = (start..start + count).rev().find(|&id| taken(id)) {
+            start = taken + 1;
+        }
+
+        replace_file(&self.next_id_file_name(),
+                     |file| write!(file, "{}", start + count))?;
+        Ok(start..start + count)
+    }
+
+    fn load(&self, id: usize) -> ado::Result<FileTask<BasicTask, F>> {
+        self.load_from(id, self.file_name(id))
+    }
+
+    /// Reads the task with the given id from a file other than its usual
+    /// one, such as its file in the archive.
+    fn load_from(&self, id: usize, file_name: PathBuf) -> ado::Result<FileTask<BasicTask, F>> {
+        let mut file = File::open(&file_name)?;
+        let content = {
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+            content
+        };
+        let modified = file.metadata()?.modified()?;
+
+        let inner = F::parse(&content, DateTime::<Utc>::from(modified))
+            .map_err(|reason| Error::MalformedTask { id, reason })?;
+        Ok(FileTask {
+            file_name,
+            inner,
+            format: PhantomData,
+            modified: Some(modified),
+        })
+    }
+}
+
+impl Format for Lines {
+    const EXTENSION: &'static str = "";
+
+    fn write(projection: &BasicTask, file: &mut File) -> Result<(), ::std::io::Error> {
+        let due = projection.due.map(|due| due.to_string()).unwrap_or_default();
+
+        // Notes are prefixed by their line count so they can span lines.
+        let note_lines = if projection.notes.is_empty() {
+            0
+        } else {
+            projection.notes.split('\n').count()
+        };
+
+        write!(file,
+               "{}\n{:?}\n{}\n{}\n{}\n{}",
+               projection.name,
+               projection.status,
+               due,
+               projection.priority,
+               projection.tags.join(","),
+               note_lines)?;
+        if note_lines != 0 {
+            write!(file, "\n{}", projection.notes)?;
+        }
+        let completed = projection.completed.map(|completed| completed.to_rfc3339());
+        let depends_on = projection.depends_on
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        let parent = projection.parent.map(|parent| parent.to_string());
+        write!(file,
+               "\n{}\n{}\n{}\n{}\n{}",
+               projection.created.to_rfc3339(),
+               completed.unwrap_or_default(),
+               depends_on.join(","),
+               parent.unwrap_or_default(),
+               projection.recurrence.as_ref().map_or("", Recurrence::name))?;
+        Ok(())
+    }
+
+    /// Files written by older versions stop early, so missing
+    /// trailing lines take their default values.
+    fn parse(content: &str, modified: DateTime<Utc>) -> Result<BasicTask, String> {
+        let lines = content.lines().collect::<Vec<_>>();
+        if lines.len() < 2 {
+            return Err(format!("expected at least 2 lines but found {}", lines.len()));
+        }
+
+        let status = lines[1].parse::<Status>()
+            .map_err(|err| err.to_string())?;
+        let due = match lines.get(2) {
+            Some(line) if !line.is_empty() => {
+                let due = line.parse::<NaiveDate>()
+                    .map_err(|err| format!("invalid due date {:?}: {}", line, err))?;
+                Some(due)
+            }
+            _ => None,
+        };
+        let priority = match lines.get(3) {
+            Some(line) if !line.is_empty() => {
+                line.parse::<u8>()
+                    .map_err(|err| format!("invalid priority {:?}: {}", line, err))?
+            }
+            _ => 0,
+        };
+        let tags = match lines.get(4) {
+            Some(line) if !line.is_empty() => line.split(',').map(String::from).collect(),
+            _ => Vec::new(),
+        };
+        let (notes, created, completed, depends_on, parent, recurrence) = match lines.get(5) {
+            Some(line) if !line.is_empty() => {
+                let count = line.parse::<usize>()
+                    .map_err(|err| format!("invalid note line count {:?}: {}", line, err))?;
+                let mut notes = lines[6..].to_vec();
+
+                // The creation and completion times, the dependencies, the
+                // parent and the recurrence follow the notes, except in
+                // older files.
+                let recurrence = if notes.len() == count + 5 {
+                    match notes.pop().unwrap() {
+                        "" => None,
+                        line => Some(line.parse::<Recurrence>().map_err(|err| err.to_string())?),
+                    }
+                } else {
+                    None
+                };
+                let parent = if notes.len() == count + 4 {
+                    match notes.pop().unwrap() {
+                        "" => None,
+                        line => {
+                            let parent = line.parse::<usize>()
+                                .map_err(|err| format!("invalid parent {:?}: {}", line, err))?;
+                            Some(parent)
+                        }
+                    }
+                } else {
+                    None
+                };
+                let depends_on = if notes.len() == count + 3 {
+                    parse_ids(notes.pop().unwrap())?
+                } else {
+                    Vec::new()
+                };
+                let completed = if notes.len() == count + 2 {
+                    match notes.pop().unwrap() {
+                        "" => None,
+                        line => Some(parse_time(line, "completion")?),
+                    }
+                } else {
+                    None
+                };
+                let created = if notes.len() == count + 1 {
+                    parse_time(notes.pop().unwrap(), "creation")?
+                } else {
+                    modified
+                };
+
+                // A trailing empty note line looks like the end of the file.
+                if notes.len() + 1 == count {
+                    notes.push("");
+                }
+                if notes.len() != count {
+                    return Err(format!("expected {} note lines but found {}",
+                                       count,
+                                       notes.len()));
+                }
+                (notes.join("\n"), created, completed, depends_on, parent, recurrence)
+            }
+            _ => {
+                if lines.len() > 6 {
+                    return Err(format!("expected at most 6 lines but found {}", lines.len()));
+                }
+                (String::new(), modified, None, Vec::new(), None, None)
+            }
+        };
+
+        Ok(BasicTask {
+            name: String::from(lines[0]),
+            status,
+            due,
+            priority,
+            tags,
+            notes,
+            created,
+            completed,
+            depends_on,
+            parent,
+            recurrence,
+        })
+    }
+}
+
+/// Parses a comma-separated list of task ids from a task file.
+fn parse_ids(line: &str) -> Result<Vec<usize>, String> {
+    if line.is_empty() {
+        return Ok(Vec::new());
+    }
+    line.split(',')
+        .map(|id| id.parse::<usize>().map_err(|err| format!("invalid dependency {:?}: {}", id, err)))
+        .collect()
+}
+
+/// Parses an RFC 3339 time from a task file, naming the kind of time
+/// in the error.
+fn parse_time(line: &str, kind: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(line)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|err| format!("invalid {} time {:?}: {}", kind, line, err))
+}
+
+#[cfg(feature = "json")]
+impl Format for Json {
+    const EXTENSION: &'static str = ".json";
+
+    fn write(task: &BasicTask, file: &mut File) -> Result<(), ::std::io::Error> {
+        ::serde_json::to_writer_pretty(file, task)
+            .map_err(::std::io::Error::from)
+    }
+
+    fn parse(content: &str, modified: DateTime<Utc>) -> Result<BasicTask, String> {
+        let mut value = ::serde_json::from_str::<::serde_json::Value>(content)
+            .map_err(|err| err.to_string())?;
+        if let Some(task) = value.as_object_mut() {
+            task.entry("created")
+                .or_insert_with(|| ::serde_json::Value::String(modified.to_rfc3339()));
+        }
+        ::serde_json::from_value(value)
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl<F: Format> Reorder for FileTodoList<F> {
+    fn move_to(&mut self, id: usize, index: usize) -> ado::Result<()> {
+        let from = self.order
+            .iter()
+            .position(|&other| other == id)
+            .ok_or(Error::NotFound(id))?;
+        let previous = self.order.clone();
+        self.order.remove(from);
+        let index = cmp::min(index, self.order.len());
+        self.order.insert(index, id);
+
+        if let Err(err) = self.save_order() {
+            self.order = previous;
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+impl<F: Format> Import for FileTodoList<F> {}
+
+impl<F: Format> Check for FileTodoList<F> {
+    /// Files which have appeared since the list was opened are checked
+    /// too, and none of the files are cached.
+    fn check(&self) -> ado::Result<CheckReport<Error>> {
+        let (ids, skipped) = ids(&self.path, F::EXTENSION)?;
+        let mut unreadable = Vec::new();
+        for id in ids {
+            if let Err(err) = self.load(id) {
+                unreadable.push((self.file_name(id), err));
+            }
+        }
+        Ok(CheckReport {
+            unreadable,
+            skipped: skipped.into_iter().map(|name| self.path.join(name)).collect(),
+        })
+    }
+}
+
+impl<F: Format> Archive for FileTodoList<F> {
+    /// The files are moved into `archive`, keeping their ids, which
+    /// aren't handed out again.
+    fn archive_done(&mut self) -> ado::Result<usize> {
+        let mut done = Vec::new();
+        for result in self.enumerate() {
+            let (id, task) = result?;
+            if task.projection().status == Status::Done {
+                done.push(id);
+            }
+        }
+        if done.is_empty() {
+            return Ok(0);
+        }
+
+        ::std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(self.archive_path())?;
+        for &id in done.iter() {
+            fs::rename(self.file_name(id), self.archive_file_name(id))?;
+            self.order.retain(|&other| other != id);
+            self.cache.remove(&id);
+        }
+        Ok(done.len())
+    }
+
+    /// The files in `archive` are read afresh, in order of id.
+    fn list_archived(&self) -> ado::Result<Vec<(usize, BasicTask)>> {
+        if !self.archive_path().exists() {
+            return Ok(Vec::new());
+        }
+        ids(&self.archive_path(), F::EXTENSION)?
+            .0
+            .into_iter()
+            .map(|id| Ok((id, self.load_from(id, self.archive_file_name(id))?.projection())))
+            .collect()
+    }
+}
+
+impl<F: Format> Reload for FileTodoList<F> {
+    /// Tasks added by other programs go at the end of the list, and
+    /// tasks whose files have become malformed are skipped, as by
+    /// `load_all`. New files are read straight away, so that a malformed
+    /// one is skipped too, but tasks which haven't been read yet are left
+    /// to be read when they are used.
+    fn reload_if_changed(&mut self) -> ado::Result<Vec<String>> {
+        let (on_disk, skipped) = ids(&self.path, F::EXTENSION)?;
+        let mut messages = skipped.iter().map(|name| not_a_task_file(name)).collect::<Vec<_>>();
+        self.skipped = skipped;
+        self.order.retain(|id| on_disk.contains(id));
+        self.cache.retain(|id, _| on_disk.contains(id));
+
+        for id in on_disk {
+            match self.cache.get(&id) {
+                Some(cell) => {
+                    let cached = cell.get().map(|task| task.modified);
+                    let modified = fs::metadata(self.file_name(id))?.modified().ok();
+                    if cached.is_none() || cached == Some(modified) {
+                        continue;
+                    }
+                }
+                None => {
+                    self.cache.insert(id, OnceCell::new());
+                    self.order.push(id);
+                }
+            }
+
+            // Only keep the latest error for each file.
+            self.load_errors.retain(|err| match *err {
+                Error::MalformedTask { id: other, .. } => other != id,
+                _ => true,
+            });
+            match self.load(id) {
+                Ok(task) => {
+                    self.cache.insert(id, OnceCell::from(task));
+                }
+                Err(err @ Error::MalformedTask { .. }) => {
+                    if self.cache.remove(&id).is_some() {
+                        self.order.retain(|&other| other != id);
+                    }
+                    messages.push(format!("Skipped: {}", err));
+                    self.load_errors.push(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(messages)
+    }
+}
+
+impl<F: Format> TodoList for FileTodoList<F> {
+    type Error = Error;
+    type Task = FileTask<BasicTask, F>;
+
+    fn create(&mut self, name: &str) -> ado::Result<usize> {
+        let task = BasicTask::named(name)?;
+        let id = self.allocate_id()?;
+
+        // Check before saving so we never overwrite an existing task's file.
+        if self.cache.contains_key(&id) {
+            return Err(Error::DuplicateTask(id));
+        }
+
+        let new_task = FileTask::new(task, self.file_name(id))?;
+        self.cache.insert(id, OnceCell::from(new_task));
+        self.order.push(id);
+
+        Ok(id)
+    }
+
+    fn create_all(&mut self, names: &[&str]) -> ado::Result<Vec<usize>> {
+        let tasks = names.iter()
+            .map(|name| BasicTask::named(name))
+            .collect::<ado::Result<Vec<_>>>()?;
+        let ids = self.allocate_ids(names.len())?;
+        if let Some(id) = ids.clone().find(|id| self.cache.contains_key(id)) {
+            return Err(Error::DuplicateTask(id));
+        }
+
+        let mut created = Vec::with_capacity(names.len());
+        for (id, task) in ids.clone().zip(tasks) {
+            match FileTask::new(task, self.file_name(id)) {
+                Ok(task) => created.push((id, task)),
+                Err(err) => {
+                    // Don't leave a partial batch behind.
+                    for &(id, _) in &created {
+                        let _ = fs::remove_file(self.file_name(id));
+                    }
+                    return Err(Error::from(err));
+                }
+            }
+        }
+
+        for (id, task) in created {
+            self.cache.insert(id, OnceCell::from(task));
+            self.order.push(id);
+        }
+        Ok(ids.collect())
+    }
+
+    /// Malformed task files are skipped, as by `load_all`.
+    fn enumerate(&self) -> ResultIter<'_, (usize, &Self::Task)> {
+        Box::new(self.order
+            .iter()
+            .filter_map(move |&id| {
+                self.loaded_or_skipped(id).map(|result| result.map(|task| (id, task)))
+            }))
+    }
+
+    /// The task's file is moved into the trash, from which it can be
+    /// brought back with `restore`.
+    fn remove(&mut self, id: usize) -> ado::Result<Self::Task> {
+        let index = self.order
+            .iter()
+            .position(|&other| other == id)
+            .ok_or(Error::NotFound(id))?;
+
+        // The file is trashed without being read, so that a malformed
+        // task can be removed too.
+        self.trash(id)?;
+        self.order.remove(index);
+
+        match self.cache.remove(&id).and_then(OnceCell::into_inner) {
+            Some(task) => Ok(task),
+            // A task which hasn't been read yet is read from the trash.
+            // It is gone from the list either way, so a malformed file
+            // is only reported once it is in the trash.
+            None => {
+                self.load_from(id, self.trash_file_name(id))
+                    .map(|task| FileTask { file_name: self.file_name(id), ..task })
+            }
+        }
+    }
+
+    fn clear(&mut self) -> ado::Result<()> {
+        // Forget each task as soon as its file is trashed, so a failure
+        // partway through leaves exactly the tasks still in the list.
+        while let Some(&id) = self.order.last() {
+            self.trash(id)?;
+            self.order.pop();
+            self.cache.remove(&id);
+        }
+        Ok(())
+    }
+
+    /// The ids come from the directory listing, without reading any
+    /// task files.
+    fn ids(&self) -> ResultIter<'_, usize> {
+        Box::new(self.order
+            .iter()
+            .cloned()
+            .map(Ok))
+    }
+
+    fn len(&self) -> ado::Result<usize> {
+        Ok(self.order.len())
+    }
+
+    /// As with `clear`, each task is forgotten as soon as its file is
+    /// trashed, so a failure partway through leaves memory matching disk.
+    /// Malformed task files are skipped, as by `enumerate`, and so are
+    /// left where they are.
+    fn retain<P>(&mut self, mut keep: P) -> ado::Result<()>
+        where P: FnMut(&Self::Task) -> bool
+    {
+        let mut removed = Vec::new();
+        for &id in self.order.iter() {
+            if let Some(task) = self.loaded_or_skipped(id) {
+                if !keep(task?) {
+                    removed.push(id);
+                }
+            }
+        }
+        for id in removed {
+            self.trash(id)?;
+            self.order.retain(|&other| other != id);
+            self.cache.remove(&id);
+        }
+        Ok(())
+    }
+
+    fn find(&self, id: usize) -> ado::Result<&Self::Task> {
+        self.loaded(id)
+    }
+
+    /// The task's file isn't read, so a task which will turn out to be
+    /// malformed still exists until it is used.
+    fn exists(&self, id: usize) -> bool {
+        self.cache.contains_key(&id)
+    }
+
+    /// A malformed task file is never read, so a list with one doesn't
+    /// count as loaded until `load_all` drops it.
+    fn is_loaded(&self) -> bool {
+        self.cache.values().all(|cell| cell.get().is_some())
+    }
+
+    fn find_mut(&mut self, id: usize) -> ado::Result<&mut Self::Task> {
+        self.loaded_mut(id)
+    }
+
+    /// Malformed task files are skipped, as by `enumerate`.
+    fn iter(&self) -> ResultIter<'_, &Self::Task> {
+        let iter = self.order
+            .iter()
+            .filter_map(move |&id| self.loaded_or_skipped(id));
+        Box::new(iter)
+    }
+
+    /// Every task is read first, and the errors for files which couldn't
+    /// be come before the tasks which could.
+    fn iter_mut(&mut self) -> ResultIter<'_, &mut Self::Task> {
+        let errors = self.load_each();
+        let iter = errors.into_iter()
+            .map(Err)
+            .chain(self.cache
+                .values_mut()
+                .filter_map(OnceCell::get_mut)
+                .map(Ok));
+        Box::new(iter)
+    }
+
+    /// As with `iter_mut`, errors come before the tasks.
+    fn into_iter<'a>(self) -> ResultIter<'a, Self::Task> {
+        let errors = self.load_each();
+        let iter = errors.into_iter()
+            .map(Err)
+            .chain(self.cache
+                .into_values()
+                .filter_map(OnceCell::into_inner)
+                .map(Ok));
+        Box::new(iter)
+    }
+}
+
+impl<'a, F: Format> IntoIterator for &'a FileTodoList<F> {
+    type Item = ado::Result<&'a FileTask<BasicTask, F>>;
+    type IntoIter = ResultIter<'a, &'a FileTask<BasicTask, F>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, F: Format> IntoIterator for &'a mut FileTodoList<F> {
+    type Item = ado::Result<&'a mut FileTask<BasicTask, F>>;
+    type IntoIter = ResultIter<'a, &'a mut FileTask<BasicTask, F>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// The directory lists are stored in unless told otherwise:
+/// `$ADO_DIR` if it is set, or `./.ado/`.
+fn default_path() -> PathBuf {
+    path_from(::std::env::var_os("ADO_DIR"))
+}
+
+/// The directory named by the value of `ADO_DIR`, or `./.ado/` if it
+/// is unset.
+fn path_from(ado_dir: Option<OsString>) -> PathBuf {
+    ado_dir.map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(PATH))
+}
+
+/// Replaces the contents of a file by writing a sibling temporary file
+/// and renaming it into place, which is atomic on the same filesystem.
+/// Readers see either the old or the new contents, never a mixture.
+fn replace_file<F>(file_name: &Path, write: F) -> Result<(), ::std::io::Error>
+    where F: FnOnce(&mut File) -> Result<(), ::std::io::Error>
+{
+    let mut temp_file_name = file_name.as_os_str().to_owned();
+    temp_file_name.push(".tmp");
+    {
+        let mut file = File::create(&temp_file_name)?;
+        write(&mut file)?;
+        file.sync_all()?;
+    }
+    fs::rename(&temp_file_name, file_name)
+}
+
+/// Lists the ids of the task files with the given extension in a
+/// directory, in ascending order.
+///
+/// Failing to read the directory or one of its entries is an error.
+/// Task files in other formats are skipped. Any other files are
+/// skipped too, and their names are returned alongside the ids unless
+/// they are files ado keeps there itself, so that the caller can report
+/// them.
+fn ids(path: &Path, extension: &str) -> ado::Result<(Vec<usize>, Vec<String>)> {
+    let mut ids = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in ::std::fs::read_dir(path)? {
+        let file_name = entry?.file_name();
+        let name = match file_name.to_str() {
+            Some(name) => name,
+            None => {
+                skipped.push(file_name.to_string_lossy().into_owned());
+                continue;
+            }
+        };
+        if is_bookkeeping_file(name) {
+            continue;
+        }
+
+        // Split e.g. "00001.json" into "00001" and ".json".
+        let (stem, suffix) = name.split_at(name.find('.').unwrap_or(name.len()));
+        match stem.parse() {
+            Ok(id) if suffix == extension => ids.push(id),
+            Ok(_) => {}
+            Err(_) => skipped.push(String::from(name)),
+        }
+    }
+
+    ids.sort();
+    skipped.sort();
+    Ok((ids, skipped))
+}
+
+/// The files ado keeps alongside the task files in a list's directory.
+const BOOKKEEPING: &[&str] = &[
+    "next_id",
+    "order",
+    "tasks.toml",
+    "keymap.toml",
+    "archive",
+    "archive.toml",
+    PICKER_STATE,
+];
+
+/// Whether a file in a list's directory is one ado keeps alongside the
+/// task files, rather than a task.
+fn is_bookkeeping_file(name: &str) -> bool {
+    BOOKKEEPING.contains(&name) || name.ends_with(".tmp") || name.starts_with('.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ado::core::FakeTodoList;
+
+    impl Reorder for FakeTodoList {
+        fn move_to(&mut self, id: usize, index: usize) -> ado::Result<()> {
+            FakeTodoList::move_to(self, id, index)
+        }
+    }
+
+    impl Reload for FakeTodoList {
+        /// Nothing else can change the list, so there is nothing to reload.
+        fn reload_if_changed(&mut self) -> ado::Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// A picker over a task for each name and status, with ids from 0.
+    fn picker(tasks: Vec<(&str, Status)>) -> TaskPicker<FakeTodoList> {
+        TaskPicker::new(FakeTodoList::with_tasks(tasks)).unwrap()
+    }
+
+    /// A picker over open tasks with the given names.
+    fn open(names: &[&str]) -> TaskPicker<FakeTodoList> {
+        picker(names.iter().map(|&name| (name, Status::Open)).collect())
+    }
+
+    /// The names of the tasks shown, in order.
+    fn names(task_picker: &TaskPicker<FakeTodoList>) -> Vec<String> {
+        task_picker.view
+            .iter()
+            .map(|&id| task_picker.tasks.find(id).unwrap().name.clone())
+            .collect()
+    }
+
+    fn status(task_picker: &TaskPicker<FakeTodoList>, id: usize) -> Status {
+        task_picker.tasks.find(id).unwrap().status.clone()
+    }
+
+    #[test]
+    fn down_moves_the_cursor_and_stops_at_the_last_task() {
+        let mut task_picker = open(&["a", "b"]);
+        handle_command(&mut task_picker, Command::Down).unwrap();
+        assert_eq!(1, task_picker.position);
+        handle_command(&mut task_picker, Command::Down).unwrap();
+        assert_eq!(1, task_picker.position);
+    }
+
+    #[test]
+    fn up_moves_the_cursor_and_stops_at_the_first_task() {
+        let mut task_picker = open(&["a", "b"]);
+        task_picker.position = 1;
+        handle_command(&mut task_picker, Command::Up).unwrap();
+        assert_eq!(0, task_picker.position);
+        handle_command(&mut task_picker, Command::Up).unwrap();
+        assert_eq!(0, task_picker.position);
+    }
+
+    #[test]
+    fn left_moves_the_current_task_to_its_previous_status() {
+        let mut task_picker = picker(vec![("a", Status::Done)]);
+        handle_command(&mut task_picker, Command::Left).unwrap();
+        assert_eq!(Status::Open, status(&task_picker, 0));
+    }
+
+    #[test]
+    fn right_moves_the_current_task_to_its_next_status() {
+        let mut task_picker = open(&["a"]);
+        handle_command(&mut task_picker, Command::Right).unwrap();
+        assert_eq!(Status::Done, status(&task_picker, 0));
+    }
+
+    #[test]
+    fn complete_finishes_the_current_task_whatever_its_status() {
+        let mut task_picker = picker(vec![("a", Status::Wont)]);
+        handle_command(&mut task_picker, Command::Complete).unwrap();
+        assert_eq!(Status::Done, status(&task_picker, 0));
+    }
+
+    #[test]
+    fn reopen_moves_the_current_task_back_to_open() {
+        let mut task_picker = picker(vec![("a", Status::Done)]);
+        handle_command(&mut task_picker, Command::Reopen).unwrap();
+        assert_eq!(Status::Open, status(&task_picker, 0));
+    }
+
+    #[test]
+    fn move_down_carries_the_current_task_down_with_the_cursor() {
+        let mut task_picker = open(&["a", "b", "c"]);
+        handle_command(&mut task_picker, Command::MoveDown).unwrap();
+        assert_eq!(vec!["b", "a", "c"], names(&task_picker));
+        assert_eq!(1, task_picker.position);
+    }
+
+    #[test]
+    fn move_up_carries_the_current_task_up_with_the_cursor() {
+        let mut task_picker = open(&["a", "b", "c"]);
+        task_picker.position = 2;
+        handle_command(&mut task_picker, Command::MoveUp).unwrap();
+        assert_eq!(vec!["a", "c", "b"], names(&task_picker));
+        assert_eq!(1, task_picker.position);
+    }
+
+    #[test]
+    fn create_adds_a_task_and_selects_it() {
+        let mut task_picker = open(&["a"]);
+        handle_command(&mut task_picker, Command::Create(String::from("b"))).unwrap();
+        assert_eq!(vec!["a", "b"], names(&task_picker));
+        assert_eq!(1, task_picker.position);
+    }
+
+    #[test]
+    fn next_match_selects_the_next_task_matching_the_search() {
+        let mut task_picker = open(&["apple", "banana", "avocado"]);
+        task_picker.search("a").unwrap();
+        handle_command(&mut task_picker, Command::NextMatch).unwrap();
+        assert_eq!(1, task_picker.position);
+    }
+
+    #[test]
+    fn previous_match_wraps_around_to_the_last_match() {
+        let mut task_picker = open(&["apple", "banana", "avocado"]);
+        task_picker.search("av").unwrap();
+        task_picker.position = 0;
+        handle_command(&mut task_picker, Command::PreviousMatch).unwrap();
+        assert_eq!(2, task_picker.position);
+    }
+
+    #[test]
+    fn top_selects_the_first_task() {
+        let mut task_picker = open(&["a", "b"]);
+        task_picker.position = 1;
+        handle_command(&mut task_picker, Command::Top).unwrap();
+        assert_eq!(0, task_picker.position);
+    }
+
+    #[test]
+    fn bottom_selects_the_last_task() {
+        let mut task_picker = open(&["a", "b", "c"]);
+        handle_command(&mut task_picker, Command::Bottom).unwrap();
+        assert_eq!(2, task_picker.position);
+    }
+
+    #[test]
+    fn goto_selects_a_position_counting_from_one() {
+        let mut task_picker = open(&["a", "b", "c"]);
+        handle_command(&mut task_picker, Command::Goto(2)).unwrap();
+        assert_eq!(1, task_picker.position);
+        handle_command(&mut task_picker, Command::Goto(10)).unwrap();
+        assert_eq!(2, task_picker.position);
+    }
+
+    #[test]
+    fn yank_copies_the_current_task() {
+        let mut task_picker = open(&["a"]);
+        handle_command(&mut task_picker, Command::Yank).unwrap();
+        assert_eq!("a", task_picker.clipboard.unwrap().name);
+    }
+
+    #[test]
+    fn paste_creates_a_copy_below_the_current_task() {
+        let mut task_picker = open(&["a", "b"]);
+        task_picker.yank().unwrap();
+        handle_command(&mut task_picker, Command::Paste).unwrap();
+        assert_eq!(vec!["a", "a", "b"], names(&task_picker));
+        assert_eq!(1, task_picker.position);
+    }
+
+    #[test]
+    fn undo_brings_back_the_last_removed_task() {
+        let mut task_picker = picker(vec![("a", Status::Done)]);
+        task_picker.remove().unwrap();
+        handle_command(&mut task_picker, Command::Undo).unwrap();
+        assert_eq!(vec!["a"], names(&task_picker));
+        assert_eq!(Status::Done, status(&task_picker, task_picker.view[0]));
+        assert!(matches!(handle_command(&mut task_picker, Command::Undo),
+                         Err(FrontError::NothingToUndo)));
+    }
+
+    #[test]
+    fn reload_keeps_the_view_and_cursor() {
+        let mut task_picker = open(&["a", "b"]);
+        task_picker.position = 1;
+        handle_command(&mut task_picker, Command::Reload).unwrap();
+        assert_eq!(vec!["a", "b"], names(&task_picker));
+        assert_eq!(1, task_picker.position);
+    }
+
+    #[test]
+    fn toggle_done_hides_and_shows_done_tasks() {
+        let mut task_picker = picker(vec![("a", Status::Open), ("b", Status::Done)]);
+        handle_command(&mut task_picker, Command::ToggleDone).unwrap();
+        assert_eq!(vec!["a"], names(&task_picker));
+        handle_command(&mut task_picker, Command::ToggleDone).unwrap();
+        assert_eq!(vec!["a", "b"], names(&task_picker));
+    }
+
+    #[test]
+    fn cycle_sort_shows_the_tasks_in_the_next_order() {
+        let mut task_picker = open(&["b", "a"]);
+        handle_command(&mut task_picker, Command::CycleSort).unwrap();
+        assert_eq!(Sort::Status, task_picker.sort);
+        handle_command(&mut task_picker, Command::CycleSort).unwrap();
+        assert_eq!(vec!["a", "b"], names(&task_picker));
+    }
+
+    #[test]
+    fn no_op_changes_nothing() {
+        let mut task_picker = open(&["a", "b"]);
+        handle_command(&mut task_picker, Command::NoOp).unwrap();
+        assert_eq!(vec!["a", "b"], names(&task_picker));
+        assert_eq!(0, task_picker.position);
+    }
+
+    #[test]
+    fn commands_which_need_the_screen_are_not_handled() {
+        let mut task_picker = open(&["a"]);
+        for command in vec![Command::PageDown, Command::PageUp, Command::Rename, Command::Details,
+                            Command::Remove, Command::Visual, Command::Search, Command::Redraw,
+                            Command::Help, Command::Quit] {
+            assert!(matches!(handle_command(&mut task_picker, command),
+                             Err(FrontError::NoSuchCommand)));
+        }
+    }
+
+    #[test]
+    fn scroll_offset_only_scrolls_to_keep_the_cursor_on_screen() {
+        // Already on screen.
+        assert_eq!(0, scroll_offset(0, 4, 5));
+        assert_eq!(3, scroll_offset(3, 5, 5));
+        // Just below the bottom row, then far below it.
+        assert_eq!(1, scroll_offset(0, 5, 5));
+        assert_eq!(16, scroll_offset(0, 20, 5));
+        // Above the top row.
+        assert_eq!(2, scroll_offset(6, 2, 5));
+    }
+
+    #[test]
+    fn only_unfinished_tasks_due_before_today_are_overdue() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let mut task = BasicTask::new("a");
+        assert!(!is_overdue(&task.view(), today));
+
+        task.due = NaiveDate::from_ymd_opt(2024, 3, 10);
+        assert!(!is_overdue(&task.view(), today));
+        task.due = NaiveDate::from_ymd_opt(2024, 3, 9);
+        assert!(is_overdue(&task.view(), today));
+        task.status = Status::Wont;
+        assert!(is_overdue(&task.view(), today));
+        task.status = Status::Done;
+        assert!(!is_overdue(&task.view(), today));
+    }
+
+    #[test]
+    fn truncate_to_width_ends_cut_text_with_an_ellipsis() {
+        assert_eq!("abcdef", truncate_to_width("abcdef", 6));
+        assert_eq!("abc...", truncate_to_width("abcdefg", 6));
+        assert_eq!("..", truncate_to_width("abcdefg", 2));
+        assert_eq!("", truncate_to_width("abc", 0));
+        // Widths count characters rather than bytes.
+        assert_eq!("ééé", truncate_to_width("ééé", 3));
+    }
+
+    #[test]
+    fn wrap_breaks_between_words_and_splits_long_words() {
+        assert_eq!(vec!["the quick", "brown fox"], wrap("the quick brown fox", 10));
+        assert_eq!(vec!["abcd", "efgh", "ij"], wrap("abcdefghij", 4));
+        assert_eq!(vec!["one", "", "two"], wrap("one\n\ntwo", 10));
+    }
+
+    #[test]
+    fn rows_keep_their_prefix_and_cut_the_rest_to_fit() {
+        let mut task = BasicTask::new("a long task name");
+        task.tags = vec![String::from("home")];
+        let full = row(">", 1, &task.view(), usize::MAX);
+        assert!(full.ends_with("a long task name home"));
+
+        let width = full.chars().count() - 1;
+        let cut = row(">", 1, &task.view(), width);
+        assert_eq!(width, cut.chars().count());
+        assert!(cut.ends_with("a long task name ..."));
+        assert!(cut.starts_with(&full[..full.find('a').unwrap()]));
+    }
+
+    #[test]
+    fn movement_skips_hidden_tasks() {
+        let mut task_picker = picker(vec![("a", Status::Open),
+                                          ("b", Status::Done),
+                                          ("c", Status::Open),
+                                          ("d", Status::Done)]);
+        task_picker.toggle_done().unwrap();
+        assert_eq!(vec!["a", "c"], names(&task_picker));
+
+        task_picker.down().unwrap();
+        assert_eq!(2, task_picker.current_id().unwrap());
+        task_picker.down().unwrap();
+        assert_eq!(2, task_picker.current_id().unwrap());
+        task_picker.up().unwrap();
+        assert_eq!(0, task_picker.current_id().unwrap());
+        task_picker.bottom().unwrap();
+        assert_eq!(2, task_picker.current_id().unwrap());
+    }
+
+    #[test]
+    fn hiding_the_current_task_moves_the_cursor_to_the_next_shown_task() {
+        let mut task_picker = picker(vec![("a", Status::Open),
+                                          ("b", Status::Done),
+                                          ("c", Status::Open)]);
+        task_picker.position = 1;
+        task_picker.toggle_done().unwrap();
+        assert_eq!(2, task_picker.current_id().unwrap());
+    }
+
+    #[test]
+    fn cycling_the_sort_keeps_the_cursor_on_the_same_task() {
+        let mut task_picker = picker(vec![("b", Status::Done), ("c", Status::Open), ("a", Status::Wont)]);
+        task_picker.cycle_sort().unwrap();
+        assert_eq!(vec!["c", "b", "a"], names(&task_picker));
+        assert_eq!(0, task_picker.current_id().unwrap());
+        task_picker.cycle_sort().unwrap();
+        assert_eq!(vec!["a", "b", "c"], names(&task_picker));
+        assert_eq!(0, task_picker.current_id().unwrap());
+        task_picker.cycle_sort().unwrap();
+        assert_eq!(Sort::List, task_picker.sort);
+        assert_eq!(vec!["b", "c", "a"], names(&task_picker));
+    }
+
+    #[test]
+    fn renaming_while_sorted_by_name_keeps_the_cursor_on_the_task() {
+        let mut task_picker = open(&["a", "b", "c"]);
+        task_picker.sort = Sort::Name;
+        task_picker.refresh_view().unwrap();
+        task_picker.rename("z").unwrap();
+        assert_eq!(vec!["b", "c", "z"], names(&task_picker));
+        assert_eq!(0, task_picker.current_id().unwrap());
+    }
+
+    #[test]
+    fn goto_id_selects_a_shown_task_and_fails_otherwise() {
+        let mut task_picker = picker(vec![("a", Status::Open), ("b", Status::Done), ("c", Status::Open)]);
+        task_picker.goto_id(2).unwrap();
+        assert_eq!(2, task_picker.position);
+
+        task_picker.toggle_done().unwrap();
+        assert!(matches!(task_picker.goto_id(1), Err(FrontError::Ado(Error::NotFound(1)))));
+        assert!(matches!(task_picker.goto_id(7), Err(FrontError::Ado(Error::NotFound(7)))));
+        assert_eq!(2, task_picker.current_id().unwrap());
+    }
+
+    #[test]
+    fn id_at_gives_the_shown_task_at_each_position() {
+        let mut task_picker = open(&["a", "b"]);
+        assert_eq!(0, task_picker.id_at(0).unwrap());
+        assert_eq!(1, task_picker.id_at(1).unwrap());
+        assert!(matches!(task_picker.id_at(2), Err(FrontError::Ado(Error::NoSuchTask))));
+
+        task_picker.tasks.remove(0).unwrap();
+        task_picker.tasks.remove(1).unwrap();
+        task_picker.refresh_view().unwrap();
+        assert!(matches!(task_picker.id_at(0), Err(FrontError::Ado(Error::NoSuchTask))));
+        assert!(matches!(task_picker.current_id(), Err(FrontError::Ado(Error::NoSuchTask))));
+    }
+
+    #[test]
+    fn clean_name_trims_and_flattens_line_breaks() {
+        assert_eq!("buy milk", clean_name("  buy milk \n"));
+        assert_eq!("a b  c", clean_name("a\nb\r\nc"));
+        assert_eq!("", clean_name(" \r\n "));
+    }
+
+    #[test]
+    fn the_picker_creates_tasks_with_cleaned_names() {
+        let mut task_picker = open(&[]);
+        let id = task_picker.create(String::from(" write\nreport \n")).unwrap();
+        assert_eq!("write report", task_picker.tasks.find(id).unwrap().name);
+    }
+
+    #[test]
+    fn the_picker_refuses_blank_names() {
+        let mut task_picker = open(&["a"]);
+        assert!(matches!(task_picker.create(String::from(" \n")),
+                         Err(FrontError::Ado(Error::EmptyName))));
+        assert_eq!(vec!["a"], names(&task_picker));
+    }
+
+    #[test]
+    fn front_errors_from_ado_have_it_as_their_source() {
+        use std::error::Error as StdError;
+
+        let err = FrontError::from(Error::NotFound(3));
+        let source = err.source().expect("Ado errors have a source");
+        assert_eq!(Error::NotFound(3).to_string(), source.to_string());
+        assert!(FrontError::NoSuchCommand.source().is_none());
+
+        let cause = ::std::io::Error::other("the disk is on fire");
+        let err = FrontError::from(Error::external(cause));
+        let io = err.source()
+            .and_then(|source| source.source())
+            .and_then(|source| source.downcast_ref::<::std::io::Error>())
+            .expect("The chain reaches the io::Error");
+        assert_eq!("the disk is on fire", io.to_string());
+    }
+
+    #[test]
+    fn tree_order_puts_children_after_their_parent_in_list_order() {
+        let parents = [(1, None), (2, Some(4)), (3, None), (4, Some(1)), (5, Some(1))];
+        assert_eq!(vec![(1, 0), (4, 1), (2, 2), (5, 1), (3, 0)], tree_order(&parents));
+    }
+
+    #[test]
+    fn tree_order_treats_missing_parents_and_cycles_as_the_top() {
+        let parents = [(1, Some(9)), (2, Some(3)), (3, Some(2))];
+        assert_eq!(vec![(1, 0), (2, 0), (3, 1)], tree_order(&parents));
+    }
+
+    #[test]
+    fn sorted_pickers_show_subtasks_under_their_parent() {
+        let mut tasks = FakeTodoList::with_tasks(vec![("z", Status::Open),
+                                                      ("b", Status::Open),
+                                                      ("a1", Status::Open)]);
+        tasks.set_parent(2, Some(0)).unwrap();
+        let mut task_picker = TaskPicker::new(tasks).unwrap();
+        // The list's own order is kept, which `ado subtask` arranges.
+        assert_eq!(vec!["z", "b", "a1"], names(&task_picker));
+        task_picker.sort = Sort::Name;
+        task_picker.refresh_view().unwrap();
+        assert_eq!(vec!["b", "z", "a1"], names(&task_picker));
+        let indented = format!("{}   a1", status_column(&Status::Open));
+        assert!(task_picker.to_string().ends_with(&indented));
+    }
+
+    #[test]
+    fn ancestors_stop_at_missing_parents_and_cycles() {
+        let mut tasks = FakeTodoList::with_tasks(vec![("a", Status::Open),
+                                                      ("b", Status::Open),
+                                                      ("c", Status::Open),
+                                                      ("d", Status::Open)]);
+        tasks.set_parent(1, Some(0)).unwrap();
+        tasks.set_parent(2, Some(1)).unwrap();
+        tasks.find_mut(3).unwrap().set_parent(Some(9)).unwrap();
+        assert_eq!(vec![1, 0], ancestors(&tasks, 2).unwrap());
+        assert!(ancestors(&tasks, 0).unwrap().is_empty());
+        assert!(ancestors(&tasks, 3).unwrap().is_empty());
+
+        // Only a hand-edited file could make a cycle.
+        tasks.find_mut(0).unwrap().set_parent(Some(2)).unwrap();
+        assert_eq!(vec![1, 0], ancestors(&tasks, 2).unwrap());
+    }
+
+    /// An empty directory for a test's list, named after the test and
+    /// removed when it is dropped.
+    pub struct TempDir(pub PathBuf);
+
+    impl TempDir {
+        pub fn new(test: &str) -> TempDir {
+            let path = ::std::env::temp_dir().join(format!("ado-{}-{}", ::std::process::id(), test));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn read(file_name: &Path) -> String {
+        fs::read_to_string(file_name).unwrap()
+    }
+
+    #[test]
+    fn create_skips_ids_whose_files_already_exist() {
+        let dir = TempDir::new("create_skips_ids_whose_files_already_exist");
+        let path = &dir.0;
+        let mut todo_list = FileTodoList::<Lines>::with_path(path).unwrap();
+        fs::write(path.join("00001"), "written by hand").unwrap();
+
+        assert_eq!(2, todo_list.create("a").unwrap());
+        assert_eq!("written by hand", read(&path.join("00001")));
+        assert!(!todo_list.exists(1));
+    }
+
+    #[test]
+    fn ids_keep_rising_after_removals_and_reopening() {
+        let dir = TempDir::new("ids_keep_rising_after_removals_and_reopening");
+        {
+            let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+            assert_eq!(1, todo_list.create("a").unwrap());
+            assert_eq!(2, todo_list.create("b").unwrap());
+            todo_list.remove(2).unwrap();
+            assert_eq!(3, todo_list.create("c").unwrap());
+            todo_list.empty_trash().unwrap();
+            todo_list.remove(3).unwrap();
+            todo_list.empty_trash().unwrap();
+        }
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        assert_eq!(4, todo_list.create("d").unwrap());
+        assert_eq!(vec![5, 6], todo_list.create_all(&["e", "f"]).unwrap());
+    }
+
+    #[test]
+    fn saving_replaces_the_file_without_leaving_a_temporary_file() {
+        let dir = TempDir::new("saving_replaces_the_file_without_leaving_a_temporary_file");
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        let id = todo_list.create("a").unwrap();
+        todo_list.find_mut(id).unwrap().rename("b").unwrap();
+
+        assert!(read(&dir.0.join("00001")).starts_with("b\n"));
+        let leftovers = fs::read_dir(&dir.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect::<Vec<_>>();
+        assert!(leftovers.is_empty(), "left behind {:?}", leftovers);
+    }
+
+    /// A list in `dir` with an open task for each name, then a malformed
+    /// task file with the next id, reopened so that nothing is cached.
+    fn with_malformed(dir: &TempDir, names: &[&str]) -> FileTodoList<Lines> {
+        {
+            let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+            todo_list.create_all(names).unwrap();
+        }
+        fs::write(dir.0.join(format!("{:05}", names.len() + 1)), "no status").unwrap();
+        FileTodoList::<Lines>::with_path(&dir.0).unwrap()
+    }
+
+    #[test]
+    fn malformed_task_files_are_errors_and_dropped_by_load_all() {
+        let dir = TempDir::new("malformed_task_files_are_errors_and_dropped_by_load_all");
+        let mut todo_list = with_malformed(&dir, &["a"]);
+        assert!(matches!(todo_list.find(2), Err(Error::MalformedTask { id: 2, .. })));
+
+        todo_list.load_all().unwrap();
+        assert!(matches!(todo_list.load_errors(), [Error::MalformedTask { id: 2, .. }]));
+        assert!(!todo_list.exists(2));
+        assert_eq!("a", todo_list.find(1).unwrap().view().name);
+    }
+
+    #[test]
+    fn with_path_creates_the_directory_and_stores_tasks_there() {
+        let dir = TempDir::new("with_path_creates_the_directory_and_stores_tasks_there");
+        let path = dir.0.join("nested").join("list");
+        let mut todo_list = FileTodoList::<Lines>::with_path(&path).unwrap();
+        let id = todo_list.create("a").unwrap();
+
+        assert!(path.is_dir());
+        assert!(read(&path.join(format!("{:05}", id))).starts_with("a\n"));
+    }
+
+    #[test]
+    fn tasks_are_stored_in_ado_dir_when_it_is_set() {
+        let dir = TempDir::new("tasks_are_stored_in_ado_dir_when_it_is_set");
+        let path = path_from(Some(dir.0.clone().into_os_string()));
+        assert_eq!(dir.0, path);
+        let mut todo_list = FileTodoList::<Lines>::with_path(path).unwrap();
+        todo_list.create("a").unwrap();
+        assert_eq!(vec!["00001"], task_files(&dir.0));
+
+        assert_eq!(PathBuf::from(PATH), path_from(None));
+    }
+
+    #[test]
+    fn unknown_ids_are_not_found() {
+        let dir = TempDir::new("unknown_ids_are_not_found");
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        todo_list.create("a").unwrap();
+
+        assert!(matches!(todo_list.find(7), Err(Error::NotFound(7))));
+        assert!(matches!(todo_list.find_mut(7), Err(Error::NotFound(7))));
+        assert!(matches!(todo_list.remove(7), Err(Error::NotFound(7))));
+        assert!(!todo_list.exists(7));
+    }
+
+    #[test]
+    fn removed_tasks_can_be_restored_from_the_trash() {
+        let dir = TempDir::new("removed_tasks_can_be_restored_from_the_trash");
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        todo_list.create_all(&["a", "b"]).unwrap();
+
+        assert_eq!("a", todo_list.remove(1).unwrap().view().name);
+        assert!(!dir.0.join("00001").exists());
+        assert!(dir.0.join(".trash").join("00001").exists());
+
+        assert_eq!(1, todo_list.restore(1).unwrap());
+        assert_eq!(vec![2, 1], todo_list.ids().collect::<ado::Result<Vec<_>>>().unwrap());
+        assert_eq!("a", todo_list.find(1).unwrap().view().name);
+        assert!(matches!(todo_list.restore(1), Err(Error::DuplicateTask(1))));
+
+        todo_list.remove(1).unwrap();
+        todo_list.empty_trash().unwrap();
+        assert!(matches!(todo_list.restore(1), Err(Error::NotFound(1))));
+    }
+
+    #[test]
+    fn removing_a_malformed_task_trashes_it_and_reports_it() {
+        let dir = TempDir::new("removing_a_malformed_task_trashes_it_and_reports_it");
+        let mut todo_list = with_malformed(&dir, &["a"]);
+
+        assert!(matches!(todo_list.remove(2), Err(Error::MalformedTask { id: 2, .. })));
+        assert!(!todo_list.exists(2));
+        assert!(dir.0.join(".trash").join("00002").exists());
+    }
+
+    #[test]
+    fn task_files_are_read_on_first_use() {
+        let dir = TempDir::new("task_files_are_read_on_first_use");
+        let todo_list = with_malformed(&dir, &["a"]);
+        fs::write(dir.0.join("00001"), "changed\nOpen\n").unwrap();
+
+        assert!(todo_list.exists(2));
+        assert_eq!("changed", todo_list.find(1).unwrap().view().name);
+    }
+
+    #[test]
+    fn enumerate_iter_and_with_status_skip_malformed_tasks() {
+        let dir = TempDir::new("enumerate_iter_and_with_status_skip_malformed_tasks");
+        let mut todo_list = with_malformed(&dir, &["a", "b"]);
+        todo_list.find_mut(2).unwrap().goto_status(Status::Done).unwrap();
+
+        let ids = todo_list.enumerate()
+            .map(|result| result.map(|(id, _)| id))
+            .collect::<ado::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(vec![1, 2], ids);
+        let names = todo_list.iter()
+            .map(|result| result.map(|task| task.view().name.clone()))
+            .collect::<ado::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(vec!["a", "b"], names);
+        let done = todo_list.with_status(Status::Done)
+            .map(|result| result.map(|(id, _)| id))
+            .collect::<ado::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(vec![2], done);
+    }
+
+    #[test]
+    fn ids_returns_the_names_of_skipped_files() {
+        let dir = TempDir::new("ids_returns_the_names_of_skipped_files");
+        for name in ["00002", "00001", "00003.json", "notes.txt", "next_id", "00001.tmp"].iter() {
+            fs::write(dir.0.join(name), "").unwrap();
+        }
+        fs::create_dir(dir.0.join(".trash")).unwrap();
+
+        let (ids, skipped) = ids(&dir.0, Lines::EXTENSION).unwrap();
+        assert_eq!(vec![1, 2], ids);
+        assert_eq!(vec!["notes.txt"], skipped);
+    }
+
+    #[test]
+    fn bookkeeping_files_are_not_tasks() {
+        assert!(is_bookkeeping_file("next_id"));
+        assert!(is_bookkeeping_file("00001.tmp"));
+        assert!(is_bookkeeping_file(".lock"));
+        assert!(!is_bookkeeping_file("00001"));
+        assert!(!is_bookkeeping_file("notes.txt"));
+    }
+
+    #[test]
+    fn check_reports_skipped_and_unreadable_files() {
+        let dir = TempDir::new("check_reports_skipped_and_unreadable_files");
+        let todo_list = with_malformed(&dir, &["a"]);
+        fs::write(dir.0.join("notes.txt"), "").unwrap();
+
+        let report = todo_list.check().unwrap();
+        assert_eq!(vec![dir.0.join("notes.txt")], report.skipped);
+        assert!(matches!(report.unreadable.as_slice(),
+                         [(path, Error::MalformedTask { id: 2, .. })] if *path == dir.0.join("00002")));
+    }
+
+    #[test]
+    fn reload_picks_up_new_changed_and_removed_files() {
+        let dir = TempDir::new("reload_picks_up_new_changed_and_removed_files");
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        todo_list.create_all(&["a", "b", "c"]).unwrap();
+
+        fs::write(dir.0.join("00001"), "a2\nOpen\n").unwrap();
+        // Writes can land within the same mtime tick, so make sure this
+        // one looks changed.
+        File::options()
+            .write(true)
+            .open(dir.0.join("00001"))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        fs::remove_file(dir.0.join("00002")).unwrap();
+        fs::write(dir.0.join("00004"), "d\nOpen\n").unwrap();
+        fs::write(dir.0.join("00005"), "no status").unwrap();
+        fs::write(dir.0.join("notes.txt"), "").unwrap();
+
+        let messages = todo_list.reload_if_changed().unwrap();
+        assert_eq!(2, messages.len());
+        assert_eq!("Skipped \"notes.txt\": not a task file", messages[0]);
+        assert!(messages[1].starts_with("Skipped: "), "{}", messages[1]);
+        let names = todo_list.enumerate()
+            .map(|result| result.map(|(id, task)| (id, task.view().name.into_owned())))
+            .collect::<ado::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(vec![(1, String::from("a2")), (3, String::from("c")), (4, String::from("d"))],
+                   names);
+        assert_eq!(vec!["notes.txt"], todo_list.skipped_files());
+    }
+
+    /// A task with every field set, including notes whose last line is
+    /// empty.
+    pub fn full_task() -> BasicTask {
+        BasicTask {
+            status: Status::Done,
+            name: String::from("a task"),
+            due: Some(NaiveDate::from_ymd_opt(2020, 1, 31).unwrap()),
+            priority: 3,
+            tags: vec![String::from("home"), String::from("phone")],
+            notes: String::from("first\n\nlast\n"),
+            created: "2020-01-02T03:04:05.678Z".parse().unwrap(),
+            completed: Some("2020-01-03T00:00:00Z".parse().unwrap()),
+            depends_on: vec![1, 4],
+            parent: Some(2),
+            recurrence: Some(Recurrence::Weekly),
+        }
+    }
+
+    /// Writes the task in the format and parses it back.
+    fn round_trip<F: Format>(test: &str, task: &BasicTask) -> BasicTask {
+        let dir = TempDir::new(test);
+        let file_name = dir.0.join("task");
+        {
+            let mut file = File::create(&file_name).unwrap();
+            F::write(task, &mut file).unwrap();
+        }
+        F::parse(&read(&file_name), Utc::now()).unwrap()
+    }
+
+    #[test]
+    fn tasks_round_trip_through_lines() {
+        let task = full_task();
+        assert_eq!(task, round_trip::<Lines>("tasks_round_trip_through_lines", &task));
+        let task = BasicTask { notes: String::new(), recurrence: None, ..full_task() };
+        assert_eq!(task, round_trip::<Lines>("tasks_round_trip_through_lines", &task));
+    }
+
+    #[test]
+    fn old_lines_files_take_the_default_for_missing_fields() {
+        let modified = "2020-01-02T03:04:05Z".parse().unwrap();
+        let task = Lines::parse("a\nOpen\n", modified).unwrap();
+        assert_eq!("a", task.name);
+        assert_eq!(Status::Open, task.status);
+        assert_eq!(modified, task.created);
+        assert_eq!(None, task.due);
+        assert!(task.notes.is_empty());
+        assert!(Lines::parse("a", modified).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn tasks_round_trip_through_json() {
+        let task = full_task();
+        assert_eq!(task, round_trip::<Json>("tasks_round_trip_through_json", &task));
+    }
+
+    #[test]
+    fn opening_the_picker_reads_no_task_files() {
+        let dir = TempDir::new("opening_the_picker_reads_no_task_files");
+        let todo_list = with_malformed(&dir, &["a", "b"]);
+        assert!(skipped_messages(&todo_list).is_empty());
+
+        let task_picker = TaskPicker::new(todo_list).unwrap();
+        assert_eq!(vec![1, 2, 3], task_picker.view);
+        assert_eq!("3 total [1/3]", summary(&task_picker).unwrap());
+        assert!(task_picker.tasks.cache.values().all(|cell| cell.get().is_none()));
+        assert!(!task_picker.tasks.is_loaded());
+    }
+
+    #[test]
+    fn the_summary_counts_statuses_once_every_task_is_read() {
+        let dir = TempDir::new("the_summary_counts_statuses_once_every_task_is_read");
+        let mut todo_list = with_malformed(&dir, &["a", "b"]);
+        todo_list.find_mut(2).unwrap().goto_status(Status::Done).unwrap();
+        todo_list.load_all().unwrap();
+        let task_picker = TaskPicker::new(todo_list).unwrap();
+        assert_eq!("1 open, 1 done, 0 wont - 2 total [1/2]", summary(&task_picker).unwrap());
+    }
+
+    /// The names of the files in a list's directory, other than the ones
+    /// ado keeps there itself.
+    fn task_files(dir: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| !is_bookkeeping_file(name))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn create_all_imports_a_hundred_names_under_contiguous_ids() {
+        let dir = TempDir::new("create_all_imports_a_hundred_names_under_contiguous_ids");
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        todo_list.create("first").unwrap();
+        let names = (0..100).map(|n| format!("task {}", n)).collect::<Vec<_>>();
+        let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let ids = todo_list.create_all(&names).unwrap();
+        assert_eq!((2..102).collect::<Vec<_>>(), ids);
+        assert_eq!(101, task_files(&dir.0).len());
+        assert_eq!("task 99", todo_list.find(101).unwrap().view().name);
+    }
+
+    #[test]
+    fn a_failed_create_all_leaves_no_new_files() {
+        let dir = TempDir::new("a_failed_create_all_leaves_no_new_files");
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        todo_list.create("first").unwrap();
+        // The third task's file can't be written where there is a directory.
+        fs::create_dir(dir.0.join("00004.tmp")).unwrap();
+
+        assert!(todo_list.create_all(&["a", "b", "c", "d"]).is_err());
+        assert_eq!(vec!["00001"], task_files(&dir.0));
+        assert_eq!(vec![1], todo_list.ids().collect::<ado::Result<Vec<_>>>().unwrap());
+    }
+
+    #[test]
+    fn set_status_all_saves_each_task_it_moves() {
+        let dir = TempDir::new("set_status_all_saves_each_task_it_moves");
+        {
+            let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+            todo_list.create_all(&["a", "b", "c"]).unwrap();
+            todo_list.find_mut(2).unwrap().goto_status(Status::Wont).unwrap();
+            assert_eq!(2, todo_list.set_status_all(Status::Open, Status::Done).unwrap());
+        }
+        let todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        let statuses = todo_list.iter()
+            .map(|task| task.map(|task| task.view().status))
+            .collect::<ado::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(vec![Status::Done, Status::Wont, Status::Done], statuses);
+    }
+
+    #[test]
+    fn a_half_written_temporary_file_is_ignored() {
+        let dir = TempDir::new("a_half_written_temporary_file_is_ignored");
+        {
+            let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+            todo_list.create("a").unwrap();
+        }
+        // As left by a save which stopped partway through writing.
+        fs::write(dir.0.join("00001.tmp"), "b\nOp").unwrap();
+
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        assert_eq!(vec![1], todo_list.ids().collect::<ado::Result<Vec<_>>>().unwrap());
+        assert!(todo_list.skipped_files().is_empty());
+        assert_eq!("a", todo_list.find(1).unwrap().view().name);
+        assert_eq!(Status::Open, todo_list.find(1).unwrap().view().status);
+
+        // The next save writes the temporary file afresh.
+        todo_list.find_mut(1).unwrap().rename("c").unwrap();
+        assert!(!dir.0.join("00001.tmp").exists());
+        assert!(read(&dir.0.join("00001")).starts_with("c\n"));
+    }
+
+    #[test]
+    fn empty_one_line_and_bad_three_line_files_are_malformed() {
+        let dir = TempDir::new("empty_one_line_and_bad_three_line_files_are_malformed");
+        for &(name, content) in [("00001", ""), ("00002", "a"), ("00003", "a\nOpen\nsoon")].iter() {
+            fs::write(dir.0.join(name), content).unwrap();
+        }
+        // A third line holds the due date since that was added.
+        fs::write(dir.0.join("00004"), "a\nOpen\n2020-01-31").unwrap();
+
+        let todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        for id in 1..4 {
+            let found = match todo_list.find(id) {
+                Err(Error::MalformedTask { id, .. }) => Some(id),
+                _ => None,
+            };
+            assert_eq!(Some(id), found);
+        }
+        assert_eq!(NaiveDate::from_ymd_opt(2020, 1, 31), todo_list.find(4).unwrap().view().due);
+    }
+
+    #[test]
+    fn lists_in_different_directories_are_independent() {
+        let first_dir = TempDir::new("lists_in_different_directories_are_independent_1");
+        let second_dir = TempDir::new("lists_in_different_directories_are_independent_2");
+        let mut first = FileTodoList::<Lines>::with_path(&first_dir.0).unwrap();
+        let mut second = FileTodoList::<Lines>::with_path(&second_dir.0).unwrap();
+
+        assert_eq!(vec![1, 2], first.create_all(&["a", "b"]).unwrap());
+        assert_eq!(1, second.create("c").unwrap());
+        first.remove(1).unwrap();
+        assert_eq!(2, second.create("d").unwrap());
+        assert_eq!(3, first.create("e").unwrap());
+
+        assert_eq!(vec!["00002", "00003"], task_files(&first_dir.0));
+        assert_eq!(vec!["00001", "00002"], task_files(&second_dir.0));
+        assert_eq!("c", second.find(1).unwrap().view().name);
+        assert_eq!("3", read(&second_dir.0.join("next_id")).trim());
+        assert_eq!("4", read(&first_dir.0.join("next_id")).trim());
+    }
+
+    #[test]
+    fn names_with_line_breaks_cant_corrupt_a_task_file() {
+        let dir = TempDir::new("names_with_line_breaks_cant_corrupt_a_task_file");
+        {
+            let todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+            let mut task_picker = TaskPicker::new(todo_list).unwrap();
+            task_picker.create(String::from("a\n")).unwrap();
+            task_picker.create(String::from("b\nDone")).unwrap();
+            assert!(matches!(task_picker.tasks.create("c\n"), Err(Error::InvalidName(_))));
+            assert!(task_picker.tasks.find_mut(1).unwrap().rename("a\nWont").is_err());
+        }
+        let todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        assert_eq!(vec!["00001", "00002"], task_files(&dir.0));
+        let tasks = todo_list.iter()
+            .map(|task| task.map(|task| (task.view().name.into_owned(), task.view().status)))
+            .collect::<ado::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(vec![(String::from("a"), Status::Open), (String::from("b Done"), Status::Open)],
+                   tasks);
+    }
+
+    #[test]
+    fn retain_trashes_the_others_and_leaves_malformed_files() {
+        let dir = TempDir::new("retain_trashes_the_others_and_leaves_malformed_files");
+        let mut todo_list = with_malformed(&dir, &["a", "b", "c"]);
+        todo_list.find_mut(2).unwrap().goto_status(Status::Done).unwrap();
+
+        todo_list.retain(|task| task.view().status == Status::Open).unwrap();
+        assert_eq!(vec![1, 3, 4], todo_list.ids().collect::<ado::Result<Vec<_>>>().unwrap());
+        assert_eq!(vec!["00001", "00003", "00004"], task_files(&dir.0));
+        assert!(dir.0.join(".trash").join("00002").exists());
+        // The malformed file is still reported once it is used.
+        assert!(matches!(todo_list.find(4), Err(Error::MalformedTask { id: 4, .. })));
+    }
+
+    /// Makes trashing the task with the given id fail, by putting a
+    /// directory with something in it where its file would go.
+    fn block_trash(dir: &TempDir, id: usize) {
+        let blocker = dir.0.join(".trash").join(format!("{:05}", id)).join("blocker");
+        fs::create_dir_all(blocker).unwrap();
+    }
+
+    #[test]
+    fn a_failed_retain_leaves_the_list_matching_the_directory() {
+        let dir = TempDir::new("a_failed_retain_leaves_the_list_matching_the_directory");
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        todo_list.create_all(&["a", "b", "c", "d"]).unwrap();
+        block_trash(&dir, 3);
+
+        assert!(todo_list.retain(|task| task.view().name == "d").is_err());
+        assert_eq!(vec![3, 4], todo_list.ids().collect::<ado::Result<Vec<_>>>().unwrap());
+        assert_eq!(vec!["00003", "00004"], task_files(&dir.0));
+    }
+
+    #[test]
+    fn clear_trashes_every_task_and_stops_consistently() {
+        let dir = TempDir::new("clear_trashes_every_task_and_stops_consistently");
+        let mut todo_list = with_malformed(&dir, &["a", "b", "c"]);
+        block_trash(&dir, 2);
+
+        assert!(todo_list.clear().is_err());
+        assert_eq!(vec![1, 2], todo_list.ids().collect::<ado::Result<Vec<_>>>().unwrap());
+        assert_eq!(vec!["00001", "00002"], task_files(&dir.0));
+
+        fs::remove_dir_all(dir.0.join(".trash").join("00002")).unwrap();
+        todo_list.clear().unwrap();
+        assert!(todo_list.is_empty().unwrap());
+        assert!(task_files(&dir.0).is_empty());
+    }
+
+    #[test]
+    fn creating_after_removing_the_highest_id_uses_a_new_id() {
+        let dir = TempDir::new("creating_after_removing_the_highest_id_uses_a_new_id");
+        let mut todo_list = FileTodoList::<Lines>::with_path(&dir.0).unwrap();
+        todo_list.create("a").unwrap();
+        let highest = todo_list.create("b").unwrap();
+        todo_list.remove(highest).unwrap();
+
+        let id = todo_list.create("c").unwrap();
+        assert!(id > highest, "{} was handed out again", id);
+        assert_eq!("a", todo_list.find(1).unwrap().view().name);
+        assert_eq!("c", todo_list.find(id).unwrap().view().name);
+        assert!(todo_list.restore(highest).is_ok());
+    }
 }
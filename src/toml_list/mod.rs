@@ -0,0 +1,543 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+use ado::{BasicTask, Error, Recurrence, ResultIter, Status, Task, TodoList};
+
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use super::{default_path, replace_file, Archive, Check, CheckReport, Import, Lock, Reload, Reorder};
+
+/// A Task source storing the whole list in a single `tasks.toml`,
+/// as an array of tables.
+///
+/// Every task shares the list's store, so changing any task rewrites
+/// the whole file. Tasks are listed in the order they appear in it.
+pub struct TomlTodoList {
+    store: Rc<RefCell<TomlStore>>,
+    tasks: BTreeMap<usize, TomlTask>,
+    /// Held until the list is dropped.
+    _lock: Lock,
+}
+
+pub struct TomlTask {
+    inner: Rc<RefCell<BasicTask>>,
+    store: Rc<RefCell<TomlStore>>,
+}
+
+struct TomlStore {
+    file_name: PathBuf,
+    next_id: usize,
+    tasks: BTreeMap<usize, Rc<RefCell<BasicTask>>>,
+    order: Vec<usize>,
+    /// The modification time of the file when it was last read or written.
+    modified: Option<SystemTime>,
+}
+
+/// The layout of `tasks.toml`.
+#[derive(Serialize, Deserialize)]
+struct TomlFile {
+    #[serde(default)]
+    next_id: usize,
+    #[serde(default)]
+    task: Vec<TomlEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TomlEntry {
+    id: usize,
+    #[serde(flatten)]
+    task: BasicTask,
+}
+
+/// Parses `tasks.toml`, using its modification time as the creation
+/// time of tasks written before it was recorded.
+fn parse(content: &str, modified: DateTime<Utc>) -> ado::Result<TomlFile> {
+    let mut table = ::toml::from_str::<::toml::Table>(content)
+        .map_err(Error::external)?;
+    if let Some(&mut ::toml::Value::Array(ref mut tasks)) = table.get_mut("task") {
+        for task in tasks.iter_mut() {
+            if let ::toml::Value::Table(ref mut task) = *task {
+                task.entry("created")
+                    .or_insert_with(|| ::toml::Value::String(modified.to_rfc3339()));
+            }
+        }
+    }
+    table.try_into().map_err(Error::external)
+}
+
+impl TomlStore {
+    /// Reads the store from the given file.
+    /// The store is empty if the file doesn't exist yet.
+    fn load(file_name: PathBuf) -> ado::Result<TomlStore> {
+        let file = match File::open(&file_name) {
+            Ok(mut file) => {
+                let mut content = String::new();
+                file.read_to_string(&mut content)?;
+                let modified = DateTime::<Utc>::from(file.metadata()?.modified()?);
+                parse(&content, modified)?
+            }
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => {
+                TomlFile {
+                    next_id: 1,
+                    task: Vec::new(),
+                }
+            }
+            Err(err) => return Err(Error::from(err)),
+        };
+
+        let mut store = TomlStore {
+            modified: modified(&file_name)?,
+            file_name,
+            next_id: file.next_id,
+            tasks: BTreeMap::new(),
+            order: Vec::new(),
+        };
+        for entry in file.task {
+            let task = Rc::new(RefCell::new(entry.task));
+            if store.tasks.insert(entry.id, task).is_some() {
+                return Err(Error::DuplicateTask(entry.id));
+            }
+            store.next_id = cmp::max(store.next_id, entry.id + 1);
+            store.order.push(entry.id);
+        }
+        Ok(store)
+    }
+
+    fn save(&mut self) -> ado::Result<()> {
+        let file = TomlFile {
+            next_id: self.next_id,
+            task: self.order
+                .iter()
+                .map(|&id| {
+                    TomlEntry {
+                        id,
+                        task: self.tasks[&id].borrow().clone(),
+                    }
+                })
+                .collect(),
+        };
+        let content = ::toml::to_string(&file).map_err(Error::external)?;
+        replace_file(&self.file_name, |file| file.write_all(content.as_bytes()))?;
+        self.modified = modified(&self.file_name)?;
+        Ok(())
+    }
+}
+
+/// The modification time of a file, or `None` if it doesn't exist.
+fn modified(file_name: &Path) -> ado::Result<Option<SystemTime>> {
+    match fs::metadata(file_name) {
+        Ok(metadata) => Ok(Some(metadata.modified()?)),
+        Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+/// A task for each of the store's tasks, sharing the store.
+fn tasks(store: &Rc<RefCell<TomlStore>>) -> BTreeMap<usize, TomlTask> {
+    store.borrow()
+        .tasks
+        .iter()
+        .map(|(&id, task)| {
+            (id,
+             TomlTask {
+                 inner: task.clone(),
+                 store: store.clone(),
+             })
+        })
+        .collect()
+}
+
+impl TomlTask {
+    /// Changes the task and saves the store, putting the task back as it
+    /// was if the file can't be written.
+    fn update<F>(&mut self, change: F) -> ado::Result<()>
+        where F: FnOnce(&mut BasicTask) -> ado::Result<()>
+    {
+        let previous = self.inner.borrow().clone();
+        change(&mut self.inner.borrow_mut())?;
+        if let Err(err) = self.store.borrow_mut().save() {
+            *self.inner.borrow_mut() = previous;
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+impl Task for TomlTask {
+    type Error = Error;
+
+    fn goto_next_status(&mut self) -> ado::Result<()> {
+        self.update(|task| task.goto_next_status())
+    }
+
+    fn goto_next_back_status(&mut self) -> ado::Result<()> {
+        self.update(|task| task.goto_next_back_status())
+    }
+
+    fn goto_status(&mut self, target: Status) -> ado::Result<()> {
+        self.update(|task| task.goto_status(target))
+    }
+
+    fn rename(&mut self, name: &str) -> ado::Result<()> {
+        self.update(|task| task.rename(name))
+    }
+
+    fn set_priority(&mut self, priority: u8) -> ado::Result<()> {
+        self.update(|task| task.set_priority(priority))
+    }
+
+    fn add_tag(&mut self, tag: &str) -> ado::Result<()> {
+        self.update(|task| task.add_tag(tag))
+    }
+
+    fn set_notes(&mut self, notes: &str) -> ado::Result<()> {
+        self.update(|task| task.set_notes(notes))
+    }
+
+    fn set_due(&mut self, due: Option<NaiveDate>) -> ado::Result<()> {
+        self.update(|task| task.set_due(due))
+    }
+
+    fn set_depends_on(&mut self, ids: &[usize]) -> ado::Result<()> {
+        self.update(|task| task.set_depends_on(ids))
+    }
+
+    fn set_parent(&mut self, parent: Option<usize>) -> ado::Result<()> {
+        self.update(|task| task.set_parent(parent))
+    }
+
+    fn set_recurrence(&mut self, recurrence: Option<Recurrence>) -> ado::Result<()> {
+        self.update(|task| task.set_recurrence(recurrence))
+    }
+
+    fn projection(&self) -> BasicTask {
+        self.inner.borrow().projection()
+    }
+}
+
+impl TomlTodoList {
+    /// Loads the list stored in `tasks.toml` in `$ADO_DIR`, or in
+    /// `./.ado/` if it is unset.
+    pub fn new() -> ado::Result<TomlTodoList> {
+        Self::with_path(default_path())
+    }
+
+    /// Loads the list from `tasks.toml` in the given directory.
+    /// The list is empty if the file doesn't exist yet.
+    pub fn with_path<P: Into<PathBuf>>(path: P) -> ado::Result<TomlTodoList> {
+        let path = path.into();
+        ::std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(&path)?;
+        let lock = Lock::acquire(&path)?;
+
+        let store = Rc::new(RefCell::new(TomlStore::load(path.join("tasks.toml"))?));
+        let tasks = tasks(&store);
+
+        Ok(TomlTodoList {
+            store,
+            tasks,
+            _lock: lock,
+        })
+    }
+}
+
+impl TomlTodoList {
+    fn archive_file_name(&self) -> PathBuf {
+        self.store.borrow().file_name.with_file_name("archive.toml")
+    }
+}
+
+impl TodoList for TomlTodoList {
+    type Error = Error;
+    type Task = TomlTask;
+
+    fn create(&mut self, name: &str) -> ado::Result<usize> {
+        let inner = Rc::new(RefCell::new(BasicTask::named(name)?));
+
+        let id = {
+            let mut store = self.store.borrow_mut();
+            let id = store.next_id;
+            store.next_id += 1;
+            store.tasks.insert(id, inner.clone());
+            store.order.push(id);
+            if let Err(err) = store.save() {
+                // Keep memory consistent with the file we failed to write.
+                store.tasks.remove(&id);
+                store.order.pop();
+                store.next_id -= 1;
+                return Err(err);
+            }
+            id
+        };
+
+        self.tasks.insert(id,
+                          TomlTask {
+                              inner,
+                              store: self.store.clone(),
+                          });
+        Ok(id)
+    }
+
+    fn enumerate(&self) -> ResultIter<'_, (usize, &Self::Task)> {
+        let order = self.store.borrow().order.clone();
+        Box::new(order.into_iter()
+            .map(move |id| Ok((id, &self.tasks[&id]))))
+    }
+
+    fn remove(&mut self, id: usize) -> ado::Result<Self::Task> {
+        let mut store = self.store.borrow_mut();
+        let removed = store.tasks.remove(&id).ok_or(Error::NotFound(id))?;
+        let index = store.order.iter().position(|&other| other == id).unwrap();
+        store.order.remove(index);
+        if let Err(err) = store.save() {
+            store.tasks.insert(id, removed);
+            store.order.insert(index, id);
+            return Err(err);
+        }
+
+        // The removed task keeps its own data, but is no longer saved.
+        Ok(self.tasks.remove(&id).unwrap())
+    }
+
+    fn len(&self) -> ado::Result<usize> {
+        Ok(self.tasks.len())
+    }
+
+    fn find(&self, id: usize) -> ado::Result<&Self::Task> {
+        self.tasks.get(&id).ok_or(Error::NotFound(id))
+    }
+
+    fn exists(&self, id: usize) -> bool {
+        self.tasks.contains_key(&id)
+    }
+
+    fn find_mut(&mut self, id: usize) -> ado::Result<&mut Self::Task> {
+        self.tasks.get_mut(&id).ok_or(Error::NotFound(id))
+    }
+
+    fn iter(&self) -> ResultIter<'_, &Self::Task> {
+        let order = self.store.borrow().order.clone();
+        Box::new(order.into_iter()
+            .map(move |id| Ok(&self.tasks[&id])))
+    }
+
+    fn iter_mut(&mut self) -> ResultIter<'_, &mut Self::Task> {
+        let order = self.store.borrow().order.clone();
+        let mut tasks = self.tasks
+            .iter_mut()
+            .collect::<BTreeMap<_, _>>();
+        Box::new(order.into_iter()
+            .map(move |id| Ok(tasks.remove(&id).unwrap())))
+    }
+
+    fn into_iter<'a>(self) -> ResultIter<'a, Self::Task> {
+        let order = self.store.borrow().order.clone();
+        let mut tasks = self.tasks;
+        Box::new(order.into_iter()
+            .map(move |id| Ok(tasks.remove(&id).unwrap())))
+    }
+}
+
+impl Reorder for TomlTodoList {
+    fn move_to(&mut self, id: usize, index: usize) -> ado::Result<()> {
+        let mut store = self.store.borrow_mut();
+        let from = store.order
+            .iter()
+            .position(|&other| other == id)
+            .ok_or(Error::NotFound(id))?;
+        let previous = store.order.clone();
+        store.order.remove(from);
+        let index = cmp::min(index, store.order.len());
+        store.order.insert(index, id);
+
+        if let Err(err) = store.save() {
+            store.order = previous;
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+impl Import for TomlTodoList {}
+
+impl Check for TomlTodoList {
+    /// The whole list is in one file, which is parsed again from disk.
+    fn check(&self) -> ado::Result<CheckReport<Error>> {
+        let file_name = self.store.borrow().file_name.clone();
+        let unreadable = match TomlStore::load(file_name.clone()) {
+            Ok(_) => Vec::new(),
+            Err(err) => vec![(file_name, err)],
+        };
+        Ok(CheckReport { unreadable, skipped: Vec::new() })
+    }
+}
+
+impl Archive for TomlTodoList {
+    /// Archived tasks are kept in `archive.toml`, next to `tasks.toml`
+    /// and in the same layout, under the ids they had in the list.
+    fn archive_done(&mut self) -> ado::Result<usize> {
+        let archive_file_name = self.archive_file_name();
+        let mut store = self.store.borrow_mut();
+        let done = store.order
+            .iter()
+            .cloned()
+            .filter(|id| store.tasks[id].borrow().status == Status::Done)
+            .collect::<Vec<_>>();
+        if done.is_empty() {
+            return Ok(0);
+        }
+
+        let mut archive = TomlStore::load(archive_file_name)?;
+        for &id in done.iter() {
+            archive.tasks.insert(id, store.tasks[&id].clone());
+            archive.order.push(id);
+        }
+        archive.save()?;
+
+        let previous = (store.tasks.clone(), store.order.clone());
+        for id in done.iter() {
+            store.tasks.remove(id);
+        }
+        store.order.retain(|id| !done.contains(id));
+        if let Err(err) = store.save() {
+            // The archive keeps its copies, which are under the same ids.
+            store.tasks = previous.0;
+            store.order = previous.1;
+            return Err(err);
+        }
+        for id in done.iter() {
+            self.tasks.remove(id);
+        }
+        Ok(done.len())
+    }
+
+    fn list_archived(&self) -> ado::Result<Vec<(usize, BasicTask)>> {
+        let archive = TomlStore::load(self.archive_file_name())?;
+        Ok(archive.order
+            .iter()
+            .map(|id| (*id, archive.tasks[id].borrow().clone()))
+            .collect())
+    }
+}
+
+impl Reload for TomlTodoList {
+    /// The whole list is reloaded if the file has changed at all, so no
+    /// file is ever skipped.
+    fn reload_if_changed(&mut self) -> ado::Result<Vec<String>> {
+        let store = {
+            let current = self.store.borrow();
+            if modified(&current.file_name)? == current.modified {
+                return Ok(Vec::new());
+            }
+            TomlStore::load(current.file_name.clone())?
+        };
+        *self.store.borrow_mut() = store;
+        self.tasks = tasks(&self.store);
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::{full_task, TempDir};
+
+    fn ids(todo_list: &TomlTodoList) -> Vec<usize> {
+        todo_list.ids().collect::<ado::Result<Vec<_>>>().unwrap()
+    }
+
+    fn names<'a, I>(tasks: I) -> Vec<String>
+        where I: Iterator<Item = ado::Result<&'a TomlTask>>
+    {
+        tasks.map(|task| task.unwrap().projection().name).collect()
+    }
+
+    #[test]
+    fn a_missing_file_is_an_empty_list() {
+        let dir = TempDir::new("toml_a_missing_file_is_an_empty_list");
+        let todo_list = TomlTodoList::with_path(&dir.0).unwrap();
+        assert!(todo_list.is_empty().unwrap());
+        assert!(!dir.0.join("tasks.toml").exists());
+    }
+
+    #[test]
+    fn changes_are_saved_to_the_file() {
+        let dir = TempDir::new("toml_changes_are_saved_to_the_file");
+        {
+            let mut todo_list = TomlTodoList::with_path(&dir.0).unwrap();
+            assert_eq!(vec![1, 2, 3], todo_list.create_all(&["a", "b", "c"]).unwrap());
+            todo_list.find_mut(2).unwrap().goto_status(Status::Done).unwrap();
+            assert_eq!("a", todo_list.remove(1).unwrap().projection().name);
+            assert!(matches!(todo_list.remove(1), Err(Error::NotFound(1))));
+            assert!(matches!(todo_list.find_mut(1), Err(Error::NotFound(1))));
+        }
+        let mut todo_list = TomlTodoList::with_path(&dir.0).unwrap();
+        assert_eq!(vec![2, 3], ids(&todo_list));
+        assert_eq!(Status::Done, todo_list.find(2).unwrap().projection().status);
+        assert!(matches!(todo_list.find(1), Err(Error::NotFound(1))));
+        // Ids aren't handed out again, even after the highest is removed.
+        todo_list.remove(3).unwrap();
+        assert_eq!(4, todo_list.create("d").unwrap());
+    }
+
+    #[test]
+    fn tasks_round_trip_through_the_file() {
+        let dir = TempDir::new("toml_tasks_round_trip_through_the_file");
+        let file_name = dir.0.join("tasks.toml");
+        let mut store = TomlStore::load(file_name.clone()).unwrap();
+        store.tasks.insert(7, Rc::new(RefCell::new(full_task())));
+        store.order.push(7);
+        store.save().unwrap();
+
+        let store = TomlStore::load(file_name).unwrap();
+        assert_eq!(vec![7], store.order);
+        assert_eq!(8, store.next_id);
+        assert_eq!(full_task(), *store.tasks[&7].borrow());
+    }
+
+    #[test]
+    fn every_iterator_follows_the_saved_order() {
+        let dir = TempDir::new("toml_every_iterator_follows_the_saved_order");
+        let mut todo_list = TomlTodoList::with_path(&dir.0).unwrap();
+        todo_list.create_all(&["a", "b", "c"]).unwrap();
+        todo_list.move_to(3, 0).unwrap();
+
+        assert_eq!(vec![3, 1, 2], ids(&todo_list));
+        assert_eq!(vec!["c", "a", "b"], names(todo_list.iter()));
+        let iter_mut = todo_list.iter_mut()
+            .map(|task| task.unwrap().projection().name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["c", "a", "b"], iter_mut);
+        let into_iter = TodoList::into_iter(todo_list)
+            .map(|task| task.unwrap().projection().name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["c", "a", "b"], into_iter);
+    }
+
+    #[test]
+    fn a_change_which_cant_be_saved_is_undone() {
+        let dir = TempDir::new("toml_a_change_which_cant_be_saved_is_undone");
+        let mut todo_list = TomlTodoList::with_path(&dir.0).unwrap();
+        let id = todo_list.create("a").unwrap();
+        let saved = fs::read_to_string(dir.0.join("tasks.toml")).unwrap();
+
+        // The temporary file can't be written where there is a directory.
+        fs::create_dir(dir.0.join("tasks.toml.tmp")).unwrap();
+        assert!(todo_list.find_mut(id).unwrap().rename("b").is_err());
+        assert!(todo_list.create("c").is_err());
+        assert_eq!("a", todo_list.find(id).unwrap().projection().name);
+        assert_eq!(vec![id], ids(&todo_list));
+        assert_eq!(saved, fs::read_to_string(dir.0.join("tasks.toml")).unwrap());
+
+        fs::remove_dir(dir.0.join("tasks.toml.tmp")).unwrap();
+        assert_eq!(2, todo_list.create("c").unwrap());
+    }
+}